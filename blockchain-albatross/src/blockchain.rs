@@ -595,7 +595,7 @@ impl Blockchain {
             // Validate slash inherents
             for fork_proof in &micro_block.extrinsics.as_ref().unwrap().fork_proofs {
                 // NOTE: if this returns None, that means that at least the previous block doesn't exist, so that fork proof is invalid anyway.
-                let (slot, _) = self.get_slot_at(fork_proof.header1.block_number, fork_proof.header1.view_number, Some(&read_txn))
+                let (slot, _) = self.get_slot_at(fork_proof.block_number(), fork_proof.view_number(), Some(&read_txn))
                     .ok_or(PushError::InvalidSuccessor)?;
 
                 if fork_proof.verify(&slot.public_key().uncompress_unchecked()).is_err() {
@@ -1356,7 +1356,7 @@ impl Blockchain {
 
     /// Expects a *verified* proof!
     pub fn inherent_from_fork_proof(&self, fork_proof: &ForkProof, txn_option: Option<&Transaction>) -> Inherent {
-        let (producer, _) = self.get_slot_at(fork_proof.header1.block_number, fork_proof.header1.view_number, txn_option)
+        let (producer, _) = self.get_slot_at(fork_proof.block_number(), fork_proof.view_number(), txn_option)
             .unwrap();
         let validator_registry = NetworkInfo::from_network_id(self.network_id).validator_registry_address().expect("No ValidatorRegistry");
         Inherent {
@@ -11,6 +11,7 @@ use nimiq_database::volatile::VolatileEnvironment;
 use nimiq_hash::{Blake2bHash, Hash};
 use nimiq_network_primitives::{networks::NetworkId};
 use nimiq_primitives::policy;
+use nimiq_primitives::slot::SlotBand;
 use nimiq_database::Environment;
 
 mod signed;
@@ -111,6 +112,20 @@ impl TemporaryBlockProducer {
     }
 }
 
+#[test]
+fn it_has_all_slots_in_the_genesis_validators() {
+    let temp_producer = TemporaryBlockProducer::new();
+    let keypair = KeyPair::from(SecretKey::deserialize_from_vec(&hex::decode(SECRET_KEY).unwrap()).unwrap());
+
+    // The unit test genesis has a single validator holding all slots.
+    let validators = temp_producer.blockchain.current_validators();
+    assert_eq!(validators.iter().count(), 1);
+    assert_eq!(validators.iter().map(|band| band.num_slots()).sum::<u16>(), policy::SLOTS);
+    assert_eq!(validators.num_slots(), policy::SLOTS);
+    assert_eq!(validators.two_third_threshold(), policy::TWO_THIRD_SLOTS);
+    assert_eq!(validators.index_of(&keypair.public), Some(0));
+}
+
 #[test]
 fn it_can_rebranch_view_changes() {
     // Build forks using two producers.
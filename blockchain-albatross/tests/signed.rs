@@ -4,10 +4,11 @@ extern crate nimiq_bls as bls;
 extern crate nimiq_hash as hash;
 extern crate nimiq_primitives as primitives;
 
-use beserial::Deserialize;
-use block_albatross::{PbftCommitMessage, PbftPrepareMessage, SignedPbftCommitMessage, SignedViewChange, ViewChange, ViewChangeProofBuilder};
-use block_albatross::signed::Message;
-use bls::bls12_381::KeyPair;
+use beserial::{Deserialize, Serialize};
+use block_albatross::{PbftCommitMessage, PbftPrepareMessage, PbftProofBuilder, SignedPbftCommitMessage, SignedPbftPrepareMessage, SignedViewChange, ViewChange, ViewChangeProof, ViewChangeProofBuilder, ViewChangeProofError, verify_signed_view_changes};
+use block_albatross::signed::{AggregateError, Message};
+use bls::bls12_381::{AggregateSignature, KeyPair};
+use bls::SecureGenerate;
 use bls::bls12_381::lazy::LazyPublicKey;
 use hash::{Blake2bHash, Hash};
 use nimiq_vrf::VrfSeed;
@@ -35,6 +36,7 @@ fn test_view_change_single_signature() {
     let mut proof_builder = ViewChangeProofBuilder::new();
     proof_builder.add_signature(&key_pair.public, policy::SLOTS, &signed_message);
     let view_change_proof = proof_builder.build();
+    assert_eq!(view_change_proof.signers().iter().collect::<Vec<_>>(), vec![0]);
 
     // verify view change proof
     let validators = ValidatorSlots::new(vec![ValidatorSlotBand::new(LazyPublicKey::from(key_pair.public), policy::SLOTS)]);
@@ -61,3 +63,352 @@ fn test_replay() {
     // verify commit - this should fail
     assert!(!signed_commit.verify(&key_pair.public));
 }
+
+#[test]
+/// Tests that a view change signature for one block number can't be used for another, i.e. that
+/// the block number is part of the signed preimage.
+fn test_view_change_replay_across_block_numbers() {
+    let key_pair = KeyPair::deserialize_from_vec(&hex::decode(SECRET_KEY).unwrap()).unwrap();
+    let view_change = ViewChange {
+        block_number: 1234,
+        new_view_number: 42,
+        prev_seed: VrfSeed::default(),
+    };
+    let other_view_change = ViewChange { block_number: 1235, ..view_change.clone() };
+    assert_ne!(view_change.hash_with_prefix(), other_view_change.hash_with_prefix());
+
+    // replay a single signature for another block number
+    let signature = view_change.sign(&key_pair.secret);
+    let replayed = SignedViewChange { message: other_view_change.clone(), signer_idx: 0, signature };
+    assert!(!replayed.verify(&key_pair.public));
+
+    // replay a whole proof for another block number
+    let signed_message = SignedViewChange::from_message(view_change.clone(), &key_pair.secret, 0);
+    let mut proof_builder = ViewChangeProofBuilder::new();
+    proof_builder.add_signature(&key_pair.public, policy::SLOTS, &signed_message);
+    let proof = proof_builder.build();
+    let validators = ValidatorSlots::new(vec![ValidatorSlotBand::new(LazyPublicKey::from(key_pair.public), policy::SLOTS)]);
+    proof.verify(&view_change, &validators, policy::TWO_THIRD_SLOTS).unwrap();
+    assert_eq!(proof.verify(&other_view_change, &validators, policy::TWO_THIRD_SLOTS), Err(ViewChangeProofError::InvalidSignature));
+}
+
+#[test]
+fn test_view_change_proof_builder_threshold() {
+    let key_pair = KeyPair::deserialize_from_vec(&hex::decode(SECRET_KEY).unwrap()).unwrap();
+    let view_change = ViewChange {
+        block_number: 1234,
+        new_view_number: 42,
+        prev_seed: VrfSeed::default(),
+    };
+
+    let mut proof_builder = ViewChangeProofBuilder::new();
+    assert_eq!(proof_builder.signed_weight(), 0);
+    assert!(!proof_builder.has_threshold(policy::TWO_THIRD_SLOTS));
+
+    let signed_message = SignedViewChange::from_message(view_change.clone(), &key_pair.secret, 0);
+    proof_builder.add_signature(&key_pair.public, policy::TWO_THIRD_SLOTS - 1, &signed_message);
+    assert_eq!(proof_builder.signed_weight(), policy::TWO_THIRD_SLOTS - 1);
+    assert!(!proof_builder.has_threshold(policy::TWO_THIRD_SLOTS));
+    assert!(proof_builder.verify(&view_change, policy::TWO_THIRD_SLOTS).is_err());
+
+    let signed_message = SignedViewChange::from_message(view_change.clone(), &key_pair.secret, 1);
+    proof_builder.add_signature(&key_pair.public, 1, &signed_message);
+    assert_eq!(proof_builder.signed_weight(), policy::TWO_THIRD_SLOTS);
+    assert!(proof_builder.has_threshold(policy::TWO_THIRD_SLOTS));
+    assert!(proof_builder.verify(&view_change, policy::TWO_THIRD_SLOTS).is_ok());
+}
+
+#[test]
+fn test_view_change_proof_builder_ignores_duplicate_signers() {
+    let key_pair = KeyPair::deserialize_from_vec(&hex::decode(SECRET_KEY).unwrap()).unwrap();
+    let view_change = ViewChange {
+        block_number: 1234,
+        new_view_number: 42,
+        prev_seed: VrfSeed::default(),
+    };
+    let signed_message = SignedViewChange::from_message(view_change.clone(), &key_pair.secret, 0);
+
+    let mut proof_builder = ViewChangeProofBuilder::new();
+    assert!(proof_builder.add_signature(&key_pair.public, policy::SLOTS, &signed_message));
+    assert!(!proof_builder.add_signature(&key_pair.public, policy::SLOTS, &signed_message));
+    assert_eq!(proof_builder.signed_weight(), policy::SLOTS);
+
+    // The signature is only aggregated once, so the proof still verifies.
+    let validators = ValidatorSlots::new(vec![ValidatorSlotBand::new(LazyPublicKey::from(key_pair.public), policy::SLOTS)]);
+    proof_builder.build().verify(&view_change, &validators, policy::TWO_THIRD_SLOTS).unwrap();
+}
+
+#[test]
+fn test_view_change_proof_merge() {
+    let key_pair = KeyPair::deserialize_from_vec(&hex::decode(SECRET_KEY).unwrap()).unwrap();
+    let view_change = ViewChange {
+        block_number: 1234,
+        new_view_number: 42,
+        prev_seed: VrfSeed::default(),
+    };
+    let num_slots = policy::SLOTS / 3;
+    let validators = ValidatorSlots::new((0..3)
+        .map(|_| ValidatorSlotBand::new(LazyPublicKey::from(key_pair.public), num_slots))
+        .collect());
+
+    let partial_proof = |signers: &[u16]| {
+        let mut proof_builder = ViewChangeProofBuilder::new();
+        for &signer_idx in signers {
+            let signed_message = SignedViewChange::from_message(view_change.clone(), &key_pair.secret, signer_idx);
+            proof_builder.add_signature(&key_pair.public, num_slots, &signed_message);
+        }
+        proof_builder.build()
+    };
+
+    // Disjoint signers are merged.
+    let mut proof = partial_proof(&[0]);
+    proof.merge(&partial_proof(&[1])).unwrap();
+    assert_eq!(proof.signers().iter().collect::<Vec<_>>(), vec![0, 1]);
+    proof.verify(&view_change, &validators, 2 * num_slots).unwrap();
+
+    // Merging a subset doesn't change the proof.
+    proof.merge(&partial_proof(&[1])).unwrap();
+    assert_eq!(proof, partial_proof(&[0, 1]));
+
+    // Merging a superset replaces the proof.
+    proof.merge(&partial_proof(&[0, 1, 2])).unwrap();
+    assert_eq!(proof, partial_proof(&[0, 1, 2]));
+    proof.verify(&view_change, &validators, policy::TWO_THIRD_SLOTS).unwrap();
+
+    // Partially overlapping signers can't be merged.
+    let mut proof = partial_proof(&[0, 1]);
+    assert_eq!(proof.merge(&partial_proof(&[1, 2])), Err(AggregateError::Overlapping));
+    assert_eq!(proof, partial_proof(&[0, 1]));
+}
+
+#[test]
+fn test_view_change_proof_serialization() {
+    let key_pair = KeyPair::deserialize_from_vec(&hex::decode(SECRET_KEY).unwrap()).unwrap();
+    let view_change = ViewChange {
+        block_number: 1234,
+        new_view_number: 42,
+        prev_seed: VrfSeed::default(),
+    };
+    let num_slots = policy::SLOTS / 3;
+    let validators = ValidatorSlots::new((0..3)
+        .map(|_| ValidatorSlotBand::new(LazyPublicKey::from(key_pair.public), num_slots))
+        .collect());
+
+    let partial_proof = |signers: &[u16]| {
+        let mut proof_builder = ViewChangeProofBuilder::new();
+        for &signer_idx in signers {
+            let signed_message = SignedViewChange::from_message(view_change.clone(), &key_pair.secret, signer_idx);
+            proof_builder.add_signature(&key_pair.public, num_slots, &signed_message);
+        }
+        proof_builder.build()
+    };
+
+    // A partially aggregated proof keeps its signers and signature.
+    let proof = partial_proof(&[0, 2]);
+    let serialized = proof.serialize_to_vec();
+    assert_eq!(serialized.len(), proof.serialized_size());
+    let mut deserialized: ViewChangeProof = Deserialize::deserialize_from_vec(&serialized).unwrap();
+    assert_eq!(deserialized, proof);
+    assert_eq!(deserialized.signers().iter().collect::<Vec<_>>(), vec![0, 2]);
+    deserialized.verify(&view_change, &validators, 2 * num_slots).unwrap();
+
+    // More signatures can be merged in after receiving it.
+    deserialized.merge(&partial_proof(&[1])).unwrap();
+    deserialized.verify(&view_change, &validators, policy::TWO_THIRD_SLOTS).unwrap();
+}
+
+#[test]
+fn test_verify_signed_view_changes() {
+    let key_pair = KeyPair::deserialize_from_vec(&hex::decode(SECRET_KEY).unwrap()).unwrap();
+    let view_change = ViewChange {
+        block_number: 1234,
+        new_view_number: 42,
+        prev_seed: VrfSeed::default(),
+    };
+    let other_view_change = ViewChange { new_view_number: 43, ..view_change.clone() };
+    let validators = ValidatorSlots::new((0..4)
+        .map(|_| ValidatorSlotBand::new(LazyPublicKey::from(key_pair.public), policy::SLOTS / 4))
+        .collect());
+
+    let mut messages = vec![
+        SignedViewChange::from_message(view_change.clone(), &key_pair.secret, 0),
+        SignedViewChange::from_message(view_change.clone(), &key_pair.secret, 1),
+        SignedViewChange::from_message(other_view_change, &key_pair.secret, 2),
+        SignedViewChange::from_message(view_change, &key_pair.secret, 3),
+    ];
+    assert_eq!(verify_signed_view_changes(&messages, &validators), Ok(()));
+
+    // Signature over a different view change
+    let mut invalid = messages.clone();
+    invalid[3].signature = messages[2].signature;
+    assert_eq!(verify_signed_view_changes(&invalid, &validators), Err(3));
+
+    // Unknown signer
+    messages[1].signer_idx = 4;
+    assert_eq!(verify_signed_view_changes(&messages, &validators), Err(1));
+}
+
+#[test]
+fn test_verify_signed_view_changes_rejects_cancelling_signatures() {
+    let key_pair = KeyPair::deserialize_from_vec(&hex::decode(SECRET_KEY).unwrap()).unwrap();
+    let view_change = ViewChange {
+        block_number: 1234,
+        new_view_number: 42,
+        prev_seed: VrfSeed::default(),
+    };
+    let validators = ValidatorSlots::new((0..2)
+        .map(|_| ValidatorSlotBand::new(LazyPublicKey::from(key_pair.public), policy::SLOTS / 2))
+        .collect());
+
+    // Both signers have the same key, so the valid signatures are equal. Moving one signature to
+    // the other makes both invalid, but their sum is still the sum of the valid ones.
+    let valid = SignedViewChange::from_message(view_change, &key_pair.secret, 0);
+    let mut messages = vec![valid.clone(), SignedViewChange { signer_idx: 1, ..valid.clone() }];
+    messages[0].signature = AggregateSignature::from_signatures(&[valid.signature, valid.signature]).0;
+    messages[1].signature = AggregateSignature::new().0;
+    assert!(!messages[0].verify(&key_pair.public));
+    assert!(!messages[1].verify(&key_pair.public));
+    assert_eq!(verify_signed_view_changes(&messages, &validators), Err(0));
+}
+
+#[test]
+fn test_pbft_proof_builder_threshold() {
+    let key_pair = KeyPair::deserialize_from_vec(&hex::decode(SECRET_KEY).unwrap()).unwrap();
+    let block_hash = "foobar".hash::<Blake2bHash>();
+    let prepare = SignedPbftPrepareMessage::from_message(PbftPrepareMessage { block_hash: block_hash.clone() }, &key_pair.secret, 0);
+    let commit = SignedPbftCommitMessage::from_message(PbftCommitMessage { block_hash: block_hash.clone() }, &key_pair.secret, 0);
+
+    let mut proof_builder = PbftProofBuilder::new();
+    assert!(proof_builder.add_prepare_signature(&key_pair.public, policy::SLOTS, &prepare));
+    assert!(proof_builder.has_prepare_threshold(policy::TWO_THIRD_SLOTS));
+    assert!(!proof_builder.has_commit_threshold(policy::TWO_THIRD_SLOTS));
+    assert!(!proof_builder.has_threshold(policy::TWO_THIRD_SLOTS));
+
+    assert!(proof_builder.add_commit_signature(&key_pair.public, policy::SLOTS, &commit));
+    assert!(proof_builder.has_threshold(policy::TWO_THIRD_SLOTS));
+
+    let validators = ValidatorSlots::new(vec![ValidatorSlotBand::new(LazyPublicKey::from(key_pair.public), policy::SLOTS)]);
+    proof_builder.verify(block_hash.clone(), &validators, policy::TWO_THIRD_SLOTS).unwrap();
+    proof_builder.build().verify(block_hash, &validators, policy::TWO_THIRD_SLOTS).unwrap();
+}
+
+#[test]
+fn test_view_change_proof_errors() {
+    let key_pair = KeyPair::deserialize_from_vec(&hex::decode(SECRET_KEY).unwrap()).unwrap();
+    let view_change = ViewChange {
+        block_number: 1234,
+        new_view_number: 42,
+        prev_seed: VrfSeed::default(),
+    };
+    let other_view_change = ViewChange { new_view_number: 43, ..view_change.clone() };
+    let validators = ValidatorSlots::new(vec![
+        ValidatorSlotBand::new(LazyPublicKey::from(key_pair.public), policy::SLOTS / 2),
+        ValidatorSlotBand::new(LazyPublicKey::from(key_pair.public), policy::SLOTS / 2),
+    ]);
+
+    let proof = |signer_idx: u16| {
+        let mut proof_builder = ViewChangeProofBuilder::new();
+        let signed_message = SignedViewChange::from_message(view_change.clone(), &key_pair.secret, signer_idx);
+        proof_builder.add_signature(&key_pair.public, policy::SLOTS / 2, &signed_message);
+        proof_builder.build()
+    };
+
+    assert_eq!(proof(0).verify(&view_change, &validators, policy::TWO_THIRD_SLOTS), Err(ViewChangeProofError::InsufficientSigners(policy::SLOTS / 2, policy::TWO_THIRD_SLOTS)));
+    assert_eq!(proof(0).verify(&other_view_change, &validators, 0), Err(ViewChangeProofError::InvalidSignature));
+    assert_eq!(proof(2).verify(&view_change, &validators, 0), Err(ViewChangeProofError::InvalidSignerIndex(2)));
+    assert_eq!(
+        ViewChangeProofError::InsufficientSigners(policy::SLOTS / 2, policy::TWO_THIRD_SLOTS).to_string(),
+        format!("Insufficient signers (got {}, want {})", policy::SLOTS / 2, policy::TWO_THIRD_SLOTS),
+    );
+}
+
+#[test]
+fn test_validator_slots_two_third_threshold() {
+    let key_pair = KeyPair::deserialize_from_vec(&hex::decode(SECRET_KEY).unwrap()).unwrap();
+    let validators = |num_slots: &[u16]| -> ValidatorSlots {
+        num_slots.iter()
+            .map(|num_slots| ValidatorSlotBand::new(LazyPublicKey::from(key_pair.public), *num_slots))
+            .collect()
+    };
+
+    let all_slots = validators(&[policy::SLOTS / 2, policy::SLOTS / 2]);
+    assert_eq!(all_slots.num_slots(), policy::SLOTS);
+    assert_eq!(all_slots.two_third_threshold(), policy::TWO_THIRD_SLOTS);
+
+    let some_slots = validators(&[1, 2, 1]);
+    assert_eq!(some_slots.num_slots(), 4);
+    assert_eq!(some_slots.two_third_threshold(), 3);
+
+    assert_eq!(validators(&[]).two_third_threshold(), 1);
+}
+
+#[test]
+fn test_view_change_proof_builder_add_signature_for() {
+    let key_pair = KeyPair::deserialize_from_vec(&hex::decode(SECRET_KEY).unwrap()).unwrap();
+    let validators = ValidatorSlots::new(vec![ValidatorSlotBand::new(LazyPublicKey::from(key_pair.public), policy::SLOTS)]);
+    let view_change = ViewChange {
+        block_number: 1234,
+        new_view_number: 42,
+        prev_seed: VrfSeed::default(),
+    };
+
+    let mut proof_builder = ViewChangeProofBuilder::new();
+
+    // The index must match the signer of the message and be a known validator.
+    let signed_message = SignedViewChange::from_message(view_change.clone(), &key_pair.secret, 0);
+    assert!(!proof_builder.add_signature_for(&validators, 1, &signed_message));
+    let unknown_signer = SignedViewChange::from_message(view_change.clone(), &key_pair.secret, 1);
+    assert!(!proof_builder.add_signature_for(&validators, 1, &unknown_signer));
+    assert_eq!(proof_builder.signed_weight(), 0);
+
+    assert!(proof_builder.add_signature_for(&validators, 0, &signed_message));
+    assert!(!proof_builder.add_signature_for(&validators, 0, &signed_message));
+    assert_eq!(proof_builder.signed_weight(), policy::SLOTS);
+    proof_builder.build().verify(&view_change, &validators, validators.two_third_threshold()).unwrap();
+}
+
+#[test]
+fn test_signed_message_accessors() {
+    let key_pair = KeyPair::deserialize_from_vec(&hex::decode(SECRET_KEY).unwrap()).unwrap();
+    let view_change = ViewChange {
+        block_number: 1234,
+        new_view_number: 42,
+        prev_seed: VrfSeed::default(),
+    };
+    let signed_view_change = SignedViewChange::from_message(view_change, &key_pair.secret, 7);
+    assert_eq!(signed_view_change.signer_index(), 7);
+    assert_eq!(signed_view_change.message().block_number, 1234);
+    assert_eq!(signed_view_change.message().new_view_number, 42);
+
+    let block_hash = "foobar".hash::<Blake2bHash>();
+    let signed_prepare = SignedPbftPrepareMessage::from_message(PbftPrepareMessage { block_hash: block_hash.clone() }, &key_pair.secret, 3);
+    assert_eq!(signed_prepare.signer_index(), 3);
+    assert_eq!(signed_prepare.message().block_hash, block_hash);
+}
+
+#[test]
+fn test_validator_slots_index_of() {
+    let key_pair = KeyPair::deserialize_from_vec(&hex::decode(SECRET_KEY).unwrap()).unwrap();
+    let other_key_pair = KeyPair::generate_default_csprng();
+    let unknown_key_pair = KeyPair::generate_default_csprng();
+
+    let validators = ValidatorSlots::new(vec![
+        ValidatorSlotBand::new(LazyPublicKey::from(other_key_pair.public), 12),
+        ValidatorSlotBand::new(key_pair.public.compress(), policy::SLOTS - 12),
+    ]);
+    assert_eq!(validators.index_of(&other_key_pair.public), Some(0));
+    assert_eq!(validators.index_of(&key_pair.public), Some(1));
+    assert_eq!(validators.index_of(&unknown_key_pair.public), None);
+
+    // The index can be used to add signatures for a validator that is only known by its key.
+    let view_change = ViewChange {
+        block_number: 1234,
+        new_view_number: 42,
+        prev_seed: VrfSeed::default(),
+    };
+    let signer_idx = validators.index_of(&key_pair.public).unwrap();
+    let signed_message = SignedViewChange::from_message(view_change, &key_pair.secret, signer_idx);
+    let mut proof_builder = ViewChangeProofBuilder::new();
+    assert!(proof_builder.add_signature_for(&validators, signer_idx, &signed_message));
+    assert_eq!(proof_builder.signed_weight(), policy::SLOTS - 12);
+}
@@ -4,7 +4,7 @@ extern crate hex;
 extern crate nimiq_hash as hash;
 extern crate nimiq_utils as utils;
 
-use ff::Field;
+use ff::{Field, PrimeField};
 use group::{CurveAffine, CurveProjective};
 use hashbrown::HashSet;
 use pairing::Engine;
@@ -196,6 +196,15 @@ impl<E: Engine> AggregatePublicKey<E> {
         self.0.p_pub.add_assign(&other.0.p_pub);
     }
 
+    /// Adds `key` multiplied by `coefficient`. Used for batch verification, where each signature
+    /// and key pair gets its own random coefficient, so that invalid signatures can't cancel each
+    /// other out. See `AggregateSignature::aggregate_weighted`.
+    pub fn aggregate_weighted(&mut self, key: &PublicKey<E>, coefficient: u64) {
+        let mut p_pub = key.p_pub;
+        p_pub.mul_assign(<E::Fr as PrimeField>::Repr::from(coefficient));
+        self.0.p_pub.add_assign(&p_pub);
+    }
+
 
     /// Verify an aggregate signature over the same message.
     pub fn verify<M: Hash>(&self, msg: &M, signature: &AggregateSignature<E>) -> bool {
@@ -244,6 +253,13 @@ impl<E: Engine> AggregateSignature<E> {
         self.0.s.add_assign(&other.0.s);
     }
 
+    /// Adds `sig` multiplied by `coefficient`, see `AggregatePublicKey::aggregate_weighted`.
+    pub fn aggregate_weighted(&mut self, sig: &Signature<E>, coefficient: u64) {
+        let mut s = sig.s;
+        s.mul_assign(<E::Fr as PrimeField>::Repr::from(coefficient));
+        self.0.s.add_assign(&s);
+    }
+
     pub fn verify<M: Hash>(&self, public_keys: &[PublicKey<E>], msgs: &[M]) -> bool {
         // Number of messages must coincide with number of public keys.
         if public_keys.len() != msgs.len() {
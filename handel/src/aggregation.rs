@@ -4,6 +4,7 @@ use std::fmt;
 use macros::upgrade_weak;
 use parking_lot::{RwLock, RwLockUpgradableReadGuard};
 use futures::{future, Future};
+use rand::thread_rng;
 
 use utils::observer::PassThroughNotifier;
 use utils::mutable_once::MutableOnce;
@@ -75,7 +76,7 @@ pub struct Aggregation<P: Protocol> {
 
 impl<P: Protocol + fmt::Debug> Aggregation<P> {
     pub fn new(protocol: P, config: Config) -> Arc<Self> {
-        let levels = Level::create_levels(protocol.partitioner());
+        let levels = Level::create_levels(protocol.partitioner(), &mut config.peer_selection_rng());
         let todos = Arc::new(TodoList::new(protocol.evaluator()));
 
         // create aggregation
@@ -133,41 +134,55 @@ impl<P: Protocol + fmt::Debug> Aggregation<P> {
         unsafe { this.self_weak.replace(Arc::downgrade(&this)) };
 
         // register timer for updates
+        Self::schedule_update(this);
+
+        // register timer for level timeouts
+        // TODO: This ignores the timeout strategy
+        Self::schedule_level_timeout(this);
+
+        // spawn thread handling TODOs
+        //tokio::spawn(Arc::clone(&this.todos).into_future());
+    }
+
+    /// Schedules the next periodic update. The delay is given by `Config::next_update_delay`, so
+    /// that updates of different nodes don't happen in sync.
+    fn schedule_update(this: &Arc<Self>) {
         let weak = Arc::downgrade(this);
-        this.timers.set_interval(AggregationTimer::Update, move || {
+        this.timers.reset_delay(AggregationTimer::Update, move || {
             let this = upgrade_weak!(weak);
             trace!("Update for {:?}", this.protocol);
-            let store = this.protocol.store();
-            let store = store.read();
-            // NOTE: Skip level 0
-            for level in this.levels.iter().skip(1) {
-                // send update
-                if let Some(multisig) = store.combined(level.id - 1) {
-                    this.send_update(multisig, &level, this.config.update_count);
+            {
+                let store = this.protocol.store();
+                let store = store.read();
+                // NOTE: Skip level 0
+                for level in this.levels.iter().skip(1) {
+                    // send update
+                    if let Some(multisig) = store.combined(level.id - 1) {
+                        this.send_update(multisig, &level, this.config.update_count);
+                    }
                 }
             }
-        }, this.config.update_interval);
+            Self::schedule_update(&this);
+        }, this.config.next_update_delay(&mut thread_rng()));
+    }
+
+    /// Schedules the timeout for the next level, after which that level is started. The delay
+    /// for each level is given by `Config::level_timeout`.
+    fn schedule_level_timeout(this: &Arc<Self>) {
+        let level = this.state.read().next_level_timeout;
+        if level >= this.num_levels() {
+            this.timers.clear_delay(&AggregationTimer::Timeout);
+            return;
+        }
 
-        // register timer for level timeouts
-        // TODO: This ignores the timeout strategy
         let weak = Arc::downgrade(this);
-        this.timers.set_interval(AggregationTimer::Timeout, move || {
+        this.timers.reset_delay(AggregationTimer::Timeout, move || {
             let this = upgrade_weak!(weak);
-            let mut state = this.state.write();
-            let level = state.next_level_timeout;
-            if level < this.num_levels() {
-                trace!("Timeout for {:?} at level {}", this.protocol, level);
-                state.next_level_timeout += 1;
-                drop(state);
-                this.start_level(level);
-            }
-            else {
-                this.timers.clear_interval(&AggregationTimer::Timeout);
-            }
-        }, this.config.timeout);
-
-        // spawn thread handling TODOs
-        //tokio::spawn(Arc::clone(&this.todos).into_future());
+            trace!("Timeout for {:?} at level {}", this.protocol, level);
+            this.state.write().next_level_timeout += 1;
+            this.start_level(level);
+            Self::schedule_level_timeout(&this);
+        }, this.config.level_timeout(level));
     }
 
     pub fn num_levels(&self) -> usize {
@@ -184,7 +199,7 @@ impl<P: Protocol + fmt::Debug> Aggregation<P> {
         level.start();
         if level.id > 0 {
             if let Some(best) = self.protocol.store().read().combined(level.id - 1) {
-                self.send_update(best, level, self.config.peer_count);
+                self.send_update(best, level, self.config.peers_at_level(level.id, level.num_peers()));
             }
         }
     }
@@ -244,7 +259,7 @@ impl<P: Protocol + fmt::Debug> Aggregation<P> {
                 let level = self.levels.get(i)
                     .unwrap_or_else(|| panic!("No level {}", i));
                 if level.update_signature_to_send(&multisig.clone().into()) { // XXX Do this without cloning
-                    self.send_update(multisig, &level, self.config.peer_count);
+                    self.send_update(multisig, &level, self.config.peers_at_level(level.id, level.num_peers()));
                 }
             }
         }
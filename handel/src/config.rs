@@ -1,20 +1,196 @@
+use std::env;
+use std::str::FromStr;
 use std::time::Duration;
 
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
 
+
+/// How the timeout grows with the aggregation level. See `Config::level_timeout`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum TimeoutScaling {
+    /// `timeout` for every level
+    Constant,
+    /// `timeout * (1 + factor * level)`
+    Linear(u32),
+    /// `timeout * base^level`
+    Exponential(u32),
+}
+
+impl Default for TimeoutScaling {
+    fn default() -> Self {
+        TimeoutScaling::Constant
+    }
+}
+
+
+/// With the `serde` feature, a config can be deserialized (e.g. from a `[handel]` TOML table).
+/// Durations are given in milliseconds and missing fields are taken from `Config::default()`.
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct Config {
     /// Number of peers contacted during an update at each level
     pub update_count: usize,
 
     /// Frequency at which updates are sent to peers
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "deserialize_millis"))]
     pub update_interval: Duration,
 
+    /// Maximum random deviation from `update_interval`, so that nodes don't send their updates
+    /// in sync. See `Config::next_update_delay`.
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "deserialize_millis"))]
+    pub update_interval_jitter: Duration,
+
     /// Timeout for levels
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "deserialize_millis"))]
     pub timeout: Duration,
 
     /// How many peers are contacted at each level
     pub peer_count: usize,
 
+    /// How the timeout grows for higher levels
+    pub timeout_scaling: TimeoutScaling,
+
+    /// Maximum number of updates in flight across all aggregations, or `None` for no limit. See
+    /// `Config::throttle`.
+    pub max_concurrent_updates: Option<usize>,
+
+    /// Seed for the order in which peers are contacted at each level, or `None` to pick a random
+    /// order. Setting a seed makes the selection reproducible, e.g. in tests, but doesn't affect
+    /// timing. See `Config::peer_selection_rng`.
+    pub peer_selection_seed: Option<u64>,
+}
+
+/// Largest network that `Config::preset` considers small.
+pub const SMALL_NETWORK_SIZE: usize = 16;
+
+/// Largest network that `Config::preset` considers medium-sized.
+pub const MEDIUM_NETWORK_SIZE: usize = 128;
+
+impl Config {
+    /// Returns a config tuned for `network_size` nodes, as a starting point for operators.
+    ///
+    /// The number of peers contacted per level grows with the number of levels, i.e.
+    /// logarithmically with the network size. Beyond that there are three breakpoints:
+    ///
+    /// * Small networks (up to `SMALL_NETWORK_SIZE` nodes) have few levels that complete quickly,
+    ///   so they use a short timeout.
+    /// * Medium-sized networks (up to `MEDIUM_NETWORK_SIZE` nodes) use the defaults.
+    /// * Large networks send updates to more peers but less often, to limit the traffic, and
+    ///   give higher levels more time, since they need to aggregate more signatures.
+    pub fn preset(network_size: usize) -> Config {
+        let peer_count = Config::num_levels(network_size) + 2;
+        let default = Config::default();
+
+        if network_size <= SMALL_NETWORK_SIZE {
+            Config {
+                peer_count,
+                timeout: Duration::from_millis(300),
+                ..default
+            }
+        } else if network_size <= MEDIUM_NETWORK_SIZE {
+            Config {
+                peer_count,
+                ..default
+            }
+        } else {
+            Config {
+                update_count: 2,
+                update_interval: Duration::from_millis(200),
+                peer_count,
+                timeout_scaling: TimeoutScaling::Linear(1),
+                ..default
+            }
+        }
+    }
+
+    /// Number of levels of the binomial tree for `network_size` nodes, i.e.
+    /// `ceil(log2(network_size))`. Networks with zero or one node have no levels.
+    pub fn num_levels(network_size: usize) -> usize {
+        network_size.next_power_of_two().trailing_zeros() as usize
+    }
+
+    /// Timeout for `level`, i.e. the time between starting the previous level and starting
+    /// `level`. Saturates instead of overflowing.
+    pub fn level_timeout(&self, level: usize) -> Duration {
+        let level = level as u32;
+        let multiplier = match self.timeout_scaling {
+            TimeoutScaling::Constant => 1,
+            TimeoutScaling::Linear(factor) => factor.saturating_mul(level).saturating_add(1),
+            TimeoutScaling::Exponential(base) => base.saturating_pow(level),
+        };
+        self.timeout.checked_mul(multiplier)
+            .unwrap_or_else(|| Duration::from_secs(u64::max_value()))
+    }
+
+    /// Delay until the next update is sent: `update_interval` plus or minus a random jitter of at
+    /// most `update_interval_jitter`, with millisecond granularity. Without jitter this is always
+    /// `update_interval`.
+    pub fn next_update_delay<R: Rng + ?Sized>(&self, rng: &mut R) -> Duration {
+        let jitter = self.update_interval_jitter.as_millis() as u64;
+        if jitter == 0 {
+            return self.update_interval;
+        }
+        let offset = Duration::from_millis(rng.gen_range(0, jitter.saturating_mul(2).saturating_add(1)));
+        (self.update_interval + offset)
+            .checked_sub(self.update_interval_jitter)
+            .unwrap_or_default()
+    }
+
+    /// Whether a new update should be deferred because `in_flight` updates are already being
+    /// sent, i.e. whether `max_concurrent_updates` has been reached.
+    pub fn throttle(&self, in_flight: usize) -> bool {
+        self.max_concurrent_updates
+            .map_or(false, |max_concurrent_updates| in_flight >= max_concurrent_updates)
+    }
+
+    /// Number of peers to contact at a level with `level_size` nodes, i.e. `peer_count` clamped
+    /// to the size of the level. The level itself is not taken into account yet.
+    pub fn peers_at_level(&self, _level: usize, level_size: usize) -> usize {
+        self.peer_count.min(level_size)
+    }
+
+    /// Random number generator used to shuffle the peers of each level, which determines the
+    /// order in which they are contacted. It is seeded with `peer_selection_seed` if set and from
+    /// the operating system otherwise.
+    pub fn peer_selection_rng(&self) -> StdRng {
+        self.peer_selection_seed
+            .map_or_else(StdRng::from_entropy, StdRng::seed_from_u64)
+    }
+
+    /// Reads the config from the environment variables `HANDEL_UPDATE_COUNT`,
+    /// `HANDEL_UPDATE_INTERVAL`, `HANDEL_UPDATE_INTERVAL_JITTER`, `HANDEL_TIMEOUT`,
+    /// `HANDEL_PEER_COUNT` and `HANDEL_MAX_CONCURRENT_UPDATES`. Durations are given in
+    /// milliseconds. Unset variables fall back to `Config::default()`, but variables that are set
+    /// and can't be parsed are an error.
+    pub fn from_env() -> Result<Config, ConfigError> {
+        let default = Config::default();
+        Ok(Config {
+            update_count: parse_var("HANDEL_UPDATE_COUNT")?.unwrap_or(default.update_count),
+            update_interval: parse_var("HANDEL_UPDATE_INTERVAL")?.map(Duration::from_millis).unwrap_or(default.update_interval),
+            update_interval_jitter: parse_var("HANDEL_UPDATE_INTERVAL_JITTER")?.map(Duration::from_millis).unwrap_or(default.update_interval_jitter),
+            timeout: parse_var("HANDEL_TIMEOUT")?.map(Duration::from_millis).unwrap_or(default.timeout),
+            peer_count: parse_var("HANDEL_PEER_COUNT")?.unwrap_or(default.peer_count),
+            timeout_scaling: default.timeout_scaling,
+            max_concurrent_updates: parse_var("HANDEL_MAX_CONCURRENT_UPDATES")?.or(default.max_concurrent_updates),
+            peer_selection_seed: default.peer_selection_seed,
+        })
+    }
+}
+
+
+/// Parses environment variable `name`, returning `None` if it isn't set.
+fn parse_var<T: FromStr>(name: &'static str) -> Result<Option<T>, ConfigError> {
+    match env::var(name) {
+        Ok(value) => value.parse()
+            .map(Some)
+            .map_err(|_| ConfigError::InvalidVar { name, value }),
+        Err(env::VarError::NotPresent) => Ok(None),
+        Err(env::VarError::NotUnicode(value)) => Err(ConfigError::InvalidVar { name, value: value.to_string_lossy().into_owned() }),
+    }
 }
 
 
@@ -23,8 +199,293 @@ impl Default for Config {
         Config {
             update_count: 1,
             update_interval: Duration::from_millis(100),
+            update_interval_jitter: Duration::from_millis(0),
             timeout: Duration::from_millis(500),
             peer_count: 10,
+            timeout_scaling: TimeoutScaling::Constant,
+            max_concurrent_updates: None,
+            peer_selection_seed: None,
+        }
+    }
+}
+
+
+#[cfg(feature = "serde")]
+fn deserialize_millis<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+    <u64 as serde::Deserialize>::deserialize(deserializer).map(Duration::from_millis)
+}
+
+
+/// Errors that can happen when building a `Config`
+#[derive(Clone, Debug, Fail, PartialEq)]
+pub enum ConfigError {
+    #[fail(display = "{} must not be zero", field)]
+    Zero { field: &'static str },
+    #[fail(display = "Invalid value for {}: {}", name, value)]
+    InvalidVar { name: &'static str, value: String },
+    #[fail(display = "update_interval_jitter must be smaller than update_interval")]
+    JitterTooLarge,
+}
+
+
+/// Builds a `Config`, starting from the defaults and validating the result.
+#[derive(Clone, Debug, Default)]
+pub struct ConfigBuilder {
+    config: Config,
+}
+
+impl ConfigBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn update_count(mut self, update_count: usize) -> Self {
+        self.config.update_count = update_count;
+        self
+    }
+
+    pub fn update_interval(mut self, update_interval: Duration) -> Self {
+        self.config.update_interval = update_interval;
+        self
+    }
+
+    pub fn update_interval_jitter(mut self, update_interval_jitter: Duration) -> Self {
+        self.config.update_interval_jitter = update_interval_jitter;
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.config.timeout = timeout;
+        self
+    }
+
+    pub fn peer_count(mut self, peer_count: usize) -> Self {
+        self.config.peer_count = peer_count;
+        self
+    }
+
+    pub fn timeout_scaling(mut self, timeout_scaling: TimeoutScaling) -> Self {
+        self.config.timeout_scaling = timeout_scaling;
+        self
+    }
+
+    pub fn max_concurrent_updates(mut self, max_concurrent_updates: usize) -> Self {
+        self.config.max_concurrent_updates = Some(max_concurrent_updates);
+        self
+    }
+
+    pub fn peer_selection_seed(mut self, peer_selection_seed: u64) -> Self {
+        self.config.peer_selection_seed = Some(peer_selection_seed);
+        self
+    }
+
+    /// Returns the config, or an error if any count or duration (other than the jitter) is zero,
+    /// if the jitter isn't smaller than the update interval, or if the timeout scales
+    /// exponentially with base zero.
+    pub fn build(self) -> Result<Config, ConfigError> {
+        let config = self.config;
+        if config.update_count == 0 {
+            return Err(ConfigError::Zero { field: "update_count" });
+        }
+        if config.update_interval == Duration::from_secs(0) {
+            return Err(ConfigError::Zero { field: "update_interval" });
+        }
+        if config.update_interval_jitter >= config.update_interval {
+            return Err(ConfigError::JitterTooLarge);
+        }
+        if config.timeout == Duration::from_secs(0) {
+            return Err(ConfigError::Zero { field: "timeout" });
+        }
+        if config.peer_count == 0 {
+            return Err(ConfigError::Zero { field: "peer_count" });
+        }
+        if config.timeout_scaling == TimeoutScaling::Exponential(0) {
+            return Err(ConfigError::Zero { field: "timeout_scaling" });
+        }
+        if config.max_concurrent_updates == Some(0) {
+            return Err(ConfigError::Zero { field: "max_concurrent_updates" });
         }
+        Ok(config)
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use rand::seq::SliceRandom;
+
+    use super::*;
+
+    #[test]
+    fn test_builder() {
+        let config = ConfigBuilder::new()
+            .update_count(2)
+            .update_interval(Duration::from_millis(50))
+            .timeout(Duration::from_secs(1))
+            .peer_count(5)
+            .build()
+            .unwrap();
+        assert_eq!(config.update_count, 2);
+        assert_eq!(config.update_interval, Duration::from_millis(50));
+        assert_eq!(config.timeout, Duration::from_secs(1));
+        assert_eq!(config.peer_count, 5);
+
+        // Unset fields keep their defaults.
+        let config = ConfigBuilder::new().peer_count(3).build().unwrap();
+        assert_eq!(config.update_count, Config::default().update_count);
+        assert_eq!(config.peer_count, 3);
+    }
+
+    #[test]
+    fn test_builder_rejects_zero_values() {
+        assert_eq!(ConfigBuilder::new().update_count(0).build().unwrap_err(), ConfigError::Zero { field: "update_count" });
+        assert_eq!(ConfigBuilder::new().update_interval(Duration::from_secs(0)).build().unwrap_err(), ConfigError::Zero { field: "update_interval" });
+        assert_eq!(ConfigBuilder::new().timeout(Duration::from_secs(0)).build().unwrap_err(), ConfigError::Zero { field: "timeout" });
+        assert_eq!(ConfigBuilder::new().peer_count(0).build().unwrap_err(), ConfigError::Zero { field: "peer_count" });
+    }
+
+    #[test]
+    fn test_level_timeout() {
+        let mut config = Config::default();
+        config.timeout = Duration::from_millis(100);
+        assert_eq!(config.level_timeout(0), Duration::from_millis(100));
+        assert_eq!(config.level_timeout(5), Duration::from_millis(100));
+
+        config.timeout_scaling = TimeoutScaling::Linear(2);
+        assert_eq!(config.level_timeout(0), Duration::from_millis(100));
+        assert_eq!(config.level_timeout(3), Duration::from_millis(700));
+
+        config.timeout_scaling = TimeoutScaling::Exponential(2);
+        assert_eq!(config.level_timeout(0), Duration::from_millis(100));
+        assert_eq!(config.level_timeout(3), Duration::from_millis(800));
+        assert_eq!(config.level_timeout(1000), Duration::from_secs(u64::max_value()));
+    }
+
+    #[test]
+    fn test_next_update_delay() {
+        let mut rng = StdRng::seed_from_u64(42);
+
+        let config = Config::default();
+        assert_eq!(config.next_update_delay(&mut rng), config.update_interval);
+
+        let config = ConfigBuilder::new()
+            .update_interval(Duration::from_millis(100))
+            .update_interval_jitter(Duration::from_millis(20))
+            .build()
+            .unwrap();
+        let delays: Vec<Duration> = (0..100).map(|_| config.next_update_delay(&mut rng)).collect();
+        assert!(delays.iter().all(|delay| *delay >= Duration::from_millis(80) && *delay <= Duration::from_millis(120)));
+        assert!(delays.iter().any(|delay| *delay != Duration::from_millis(100)));
+
+        assert_eq!(ConfigBuilder::new().update_interval_jitter(Duration::from_secs(1)).build().unwrap_err(), ConfigError::JitterTooLarge);
+    }
+
+    #[test]
+    fn test_preset() {
+        let small = Config::preset(8);
+        assert_eq!(small.peer_count, 5);
+        assert!(small.timeout < Config::default().timeout);
+
+        let medium = Config::preset(MEDIUM_NETWORK_SIZE);
+        assert_eq!(medium.peer_count, 9);
+        assert_eq!(medium.timeout, Config::default().timeout);
+
+        let large = Config::preset(512);
+        assert_eq!(large.peer_count, 11);
+        assert_eq!(large.update_count, 2);
+        assert_eq!(large.timeout_scaling, TimeoutScaling::Linear(1));
+
+        // Presets are valid configs, even for degenerate network sizes.
+        for &network_size in &[0, 1, SMALL_NETWORK_SIZE, MEDIUM_NETWORK_SIZE + 1, 10_000] {
+            let config = Config::preset(network_size);
+            assert!(ConfigBuilder { config }.build().is_ok());
+        }
+    }
+
+    #[test]
+    fn test_num_levels() {
+        assert_eq!(Config::num_levels(0), 0);
+        assert_eq!(Config::num_levels(1), 0);
+        assert_eq!(Config::num_levels(2), 1);
+        assert_eq!(Config::num_levels(3), 2);
+        assert_eq!(Config::num_levels(1000), 10);
+        assert_eq!(Config::num_levels(1024), 10);
+        assert_eq!(Config::num_levels(1025), 11);
+    }
+
+    #[test]
+    fn test_peers_at_level() {
+        let config = ConfigBuilder::new().peer_count(4).build().unwrap();
+        assert_eq!(config.peers_at_level(0, 1), 1);
+        assert_eq!(config.peers_at_level(2, 4), 4);
+        assert_eq!(config.peers_at_level(5, 32), 4);
+        assert_eq!(config.peers_at_level(0, 0), 0);
+    }
+
+    #[test]
+    fn test_throttle() {
+        let config = Config::default();
+        assert!(!config.throttle(0));
+        assert!(!config.throttle(usize::max_value()));
+
+        let config = ConfigBuilder::new().max_concurrent_updates(2).build().unwrap();
+        assert!(!config.throttle(0));
+        assert!(!config.throttle(1));
+        assert!(config.throttle(2));
+        assert!(config.throttle(3));
+
+        assert_eq!(ConfigBuilder::new().max_concurrent_updates(0).build().unwrap_err(), ConfigError::Zero { field: "max_concurrent_updates" });
+    }
+
+    #[test]
+    fn test_peer_selection_rng() {
+        let peers = |config: &Config| {
+            let mut peers: Vec<usize> = (0..32).collect();
+            peers.shuffle(&mut config.peer_selection_rng());
+            peers
+        };
+
+        let config = ConfigBuilder::new().peer_selection_seed(42).build().unwrap();
+        assert_eq!(peers(&config), peers(&config));
+        assert_ne!(peers(&config), (0..32).collect::<Vec<usize>>());
+
+        let other_config = ConfigBuilder::new().peer_selection_seed(43).build().unwrap();
+        assert_ne!(peers(&config), peers(&other_config));
+    }
+
+    #[test]
+    fn test_from_env() {
+        env::set_var("HANDEL_PEER_COUNT", "3");
+        env::set_var("HANDEL_TIMEOUT", "1000");
+        let config = Config::from_env().unwrap();
+        assert_eq!(config.peer_count, 3);
+        assert_eq!(config.timeout, Duration::from_secs(1));
+        assert_eq!(config.update_count, Config::default().update_count);
+
+        env::set_var("HANDEL_TIMEOUT", "5oo");
+        assert_eq!(Config::from_env().unwrap_err(), ConfigError::InvalidVar { name: "HANDEL_TIMEOUT", value: String::from("5oo") });
+
+        env::remove_var("HANDEL_PEER_COUNT");
+        env::remove_var("HANDEL_TIMEOUT");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_deserialize_from_toml() {
+        let config: Config = toml::from_str(r#"
+            update_count = 2
+            update_interval = 50
+            timeout = 1000
+            timeout_scaling = { linear = 2 }
+        "#).unwrap();
+        assert_eq!(config.update_count, 2);
+        assert_eq!(config.update_interval, Duration::from_millis(50));
+        assert_eq!(config.timeout, Duration::from_secs(1));
+        assert_eq!(config.peer_count, Config::default().peer_count);
+        assert_eq!(config.timeout_scaling, TimeoutScaling::Linear(2));
+
+        let config: Config = toml::from_str("").unwrap();
+        assert_eq!(config.timeout, Config::default().timeout);
     }
 }
@@ -1,8 +1,8 @@
 use std::cmp::min;
 use std::sync::Arc;
 
+use rand::Rng;
 use rand::seq::SliceRandom;
-use rand::thread_rng;
 use parking_lot::RwLock;
 
 use crate::partitioner::{Partitioner, PartitioningError};
@@ -46,17 +46,18 @@ impl Level {
         self.peer_ids.len()
     }
 
-    pub fn create_levels<P: Partitioner>(partitioner: Arc<P>) -> Vec<Level> {
+    /// Creates the levels for `partitioner`. The peers of each level are shuffled with `rng`,
+    /// which determines the order in which `select_next_peers` returns them.
+    pub fn create_levels<P: Partitioner, R: Rng + ?Sized>(partitioner: Arc<P>, rng: &mut R) -> Vec<Level> {
         let mut levels: Vec<Level> = Vec::new();
         let mut first_active = false;
         let mut send_expected_full_size: usize = 1;
-        let mut rng = thread_rng();
 
         for i in 0 .. partitioner.levels() {
             match partitioner.range(i) {
                 Ok(ids) => {
                     let mut ids = ids.collect::<Vec<usize>>();
-                    ids.shuffle(&mut rng);
+                    ids.shuffle(rng);
 
                     let size = ids.len();
                     trace!("Level {} peers: {:?}", i, ids);
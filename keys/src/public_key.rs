@@ -19,7 +19,12 @@ impl PublicKey {
     pub const SIZE: usize = 32;
 
     pub fn verify(&self, signature: &Signature, data: &[u8]) -> bool {
-        self.as_dalek().verify(data, signature.as_dalek()).is_ok()
+        self.try_verify(signature, data).is_ok()
+    }
+
+    /// Like `verify`, but returns the reason why the signature is invalid.
+    pub fn try_verify(&self, signature: &Signature, data: &[u8]) -> Result<(), KeysError> {
+        self.as_dalek().verify(data, signature.as_dalek()).map_err(KeysError)
     }
 
     #[inline]
@@ -8,6 +8,12 @@ use failure::Fail;
 
 use beserial::{Deserialize, ReadBytesExt, Serialize, SerializingError, WriteBytesExt};
 
+/// IP address of a peer, as seen by us or by the peer that relayed it.
+///
+/// IPv6 zone identifiers (e.g. `eth0` in `fe80::1%eth0`) are not part of a `NetAddress`: they
+/// are only meaningful on the host they were assigned on, so they are neither transmitted nor
+/// signed. Code that dials link-local addresses keeps the zone identifier in the host name
+/// instead, see `NetAddress::parse_with_zone_id`.
 #[derive(Debug, Ord, PartialOrd, PartialEq, Eq, Hash, Clone, Copy)]
 pub enum NetAddress {
     IPv4(Ipv4Addr),
@@ -45,6 +51,14 @@ impl NetAddress {
         }
     }
 
+    /// Whether both addresses are IP addresses of the same family and agree in the first
+    /// `bit_count` bits. Pseudo addresses are never in the same subnet.
+    pub fn is_in_same_subnet(&self, other: &NetAddress, bit_count: u8) -> bool {
+        !self.is_pseudo()
+            && self.get_type() == other.get_type()
+            && self.subnet(bit_count) == other.subnet(bit_count)
+    }
+
     pub fn is_pseudo(&self) -> bool {
         let ty = self.get_type();
         ty == NetAddressType::Unknown || ty == NetAddressType::Unspecified
@@ -55,6 +69,74 @@ impl NetAddress {
         !self.is_pseudo()
     }
 
+    /// Parses an IP address that may carry an IPv6 zone identifier, like `fe80::1%eth0`, and
+    /// returns the zone identifier separately. Zone identifiers are rejected for IPv4 addresses.
+    pub fn parse_with_zone_id(s: &str) -> Result<(NetAddress, Option<String>), NetAddressParseError> {
+        match split_zone_id(s) {
+            (addr, Some(zone_id)) if !zone_id.is_empty() => {
+                let addr: Ipv6Addr = addr.parse().map_err(NetAddressParseError)?;
+                Ok((NetAddress::IPv6(addr), Some(String::from(zone_id))))
+            },
+            _ => {
+                let addr: IpAddr = s.parse().map_err(NetAddressParseError)?;
+                Ok((NetAddress::from(addr), None))
+            },
+        }
+    }
+
+    /// Whether this is a loopback address, i.e. in `127.0.0.0/8` or `::1`.
+    pub fn is_loopback(&self) -> bool {
+        self.canonical_ip().map_or(false, |ip| ip.is_loopback())
+    }
+
+    /// Whether this address is only reachable within a local network, i.e. it is in one of the
+    /// private (`10.0.0.0/8`, `172.16.0.0/12`, `192.168.0.0/16`), shared (`100.64.0.0/10`) or
+    /// link-local (`169.254.0.0/16`) IPv4 ranges, or a unique local (`fc00::/7`) or link-local
+    /// (`fe80::/10`) IPv6 address.
+    pub fn is_private(&self) -> bool {
+        match self.canonical_ip() {
+            Some(IpAddr::V4(ip)) => {
+                let octets = ip.octets();
+                ip.is_private() || ip.is_link_local()
+                    || (octets[0] == 100 && (octets[1] & 0xc0) == 64)
+            },
+            Some(IpAddr::V6(ip)) => {
+                let octets = ip.octets();
+                (octets[0] & 0xfe) == 0xfc
+                    || (octets[0] == 0xfe && (octets[1] & 0xc0) == 0x80)
+            },
+            None => false,
+        }
+    }
+
+    /// Whether this address is globally routable, i.e. it is neither a pseudo address, nor a
+    /// loopback or private address, nor in any other range reserved for special use. Only such
+    /// addresses should be advertised to other peers.
+    pub fn is_global(&self) -> bool {
+        match self.canonical_ip() {
+            // https://github.com/rust-lang/rust/issues/57558
+            Some(IpAddr::V4(ip)) if ip.octets()[0] == 0 => false,
+            Some(ip) => !self.is_loopback() && !self.is_private() && ip.is_global(),
+            None => false,
+        }
+    }
+
+    /// The IP address, with IPv4-mapped IPv6 addresses (`::ffff:a.b.c.d`) converted to IPv4, so
+    /// that they are classified like the IPv4 address they represent.
+    fn canonical_ip(&self) -> Option<IpAddr> {
+        match self {
+            NetAddress::IPv6(ip) => {
+                let segments = ip.segments();
+                if segments[..5] == [0; 5] && segments[5] == 0xffff {
+                    ip.to_ipv4().map(IpAddr::V4)
+                } else {
+                    Some(IpAddr::V6(*ip))
+                }
+            },
+            _ => self.into_ip_address(),
+        }
+    }
+
     pub fn into_ip_address(self) -> Option<IpAddr> {
         match self {
             NetAddress::IPv4(addr) => Some(IpAddr::V4(addr)),
@@ -64,6 +146,14 @@ impl NetAddress {
     }
 }
 
+/// Splits `s` at the first `%` into an address and an IPv6 zone identifier.
+pub fn split_zone_id(s: &str) -> (&str, Option<&str>) {
+    match s.find('%') {
+        Some(i) => (&s[..i], Some(&s[i + 1..])),
+        None => (s, None),
+    }
+}
+
 fn ip_to_subnet(ip: &[u8], mut bit_count: u8) -> Vec<u8> {
     let mut mask: Vec<u8> = Vec::new();
     for &byte in ip {
@@ -144,14 +234,20 @@ impl fmt::Display for NetAddress {
 #[fail(display = "{}", _0)]
 pub struct NetAddressParseError(#[cause] AddrParseError);
 
+/// Parses an IP address. An IPv6 zone identifier is accepted, but dropped.
 impl FromStr for NetAddress {
     type Err = NetAddressParseError;
 
     fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
-        let addr: IpAddr = s.parse().map_err(NetAddressParseError)?;
+        NetAddress::parse_with_zone_id(s).map(|(addr, _)| addr)
+    }
+}
+
+impl From<IpAddr> for NetAddress {
+    fn from(addr: IpAddr) -> Self {
         match addr {
-            IpAddr::V4(addr) => Ok(NetAddress::IPv4(addr)),
-            IpAddr::V6(addr) => Ok(NetAddress::IPv6(addr)),
+            IpAddr::V4(addr) => NetAddress::IPv4(addr),
+            IpAddr::V6(addr) => NetAddress::IPv6(addr),
         }
     }
 }
\ No newline at end of file
@@ -1,16 +1,20 @@
-use beserial::{Deserialize, DeserializeWithLength, ReadBytesExt, Serialize, SerializeWithLength, SerializingError, WriteBytesExt};
-use keys::{PublicKey, Signature};
+use beserial::{Deserialize, ReadBytesExt, Serialize, SerializeWithLength, SerializingError, WriteBytesExt};
+use failure::Fail;
+use keys::{KeyPair, KeysError, PublicKey, Signature};
 use std::fmt;
+use std::io;
 use std::hash::Hash;
 use std::hash::Hasher;
 use std::time::Duration;
 use std::time::SystemTime;
 use std::time::UNIX_EPOCH;
 use std::vec::Vec;
-use std::net::IpAddr;
+use std::net::{IpAddr, Ipv6Addr};
 use std::str::FromStr;
+use std::sync::atomic::{AtomicU8, Ordering};
 
 use crate::address::{NetAddress, PeerId, PeerUri};
+use crate::address::peer_uri::PeerUriError;
 use crate::protocol::Protocol;
 use crate::services::ServiceFlags;
 use super::is_ip_globally_reachable_legacy;
@@ -21,19 +25,144 @@ pub enum PeerAddressType {
     Ws(String, u16),
     Wss(String, u16),
     Rtc,
+    /// Tor v3 hidden service, the host is the `.onion` address.
+    Onion(String, u16),
+    /// WebRTC peer whose offers and answers are routed through the signaling peer with this ID.
+    /// Uses `Protocol::Rtc`, but has its own type tag on the wire, see `RTC_SIGNALED_TAG`.
+    RtcSignaled(PeerId),
 }
 
+/// Type tag of `PeerAddressType::RtcSignaled` on the wire. The other address types are tagged
+/// with their `Protocol`, but `Rtc` addresses without a signaling peer must keep their format for
+/// older clients. Those don't know this tag and reject these addresses instead of misparsing them.
+pub const RTC_SIGNALED_TAG: u8 = 16;
+
 impl PeerAddressType {
     pub fn protocol(&self) -> Protocol {
         match self {
             PeerAddressType::Dumb => Protocol::Dumb,
             PeerAddressType::Ws(_, _) => Protocol::Ws,
             PeerAddressType::Wss(_, _) => Protocol::Wss,
-            PeerAddressType::Rtc => Protocol::Rtc
+            PeerAddressType::Rtc | PeerAddressType::RtcSignaled(_) => Protocol::Rtc,
+            PeerAddressType::Onion(_, _) => Protocol::Onion,
+        }
+    }
+
+    /// Host and port to dial for websocket addresses. Other addresses can't be dialed directly,
+    /// including onion addresses, which must be reached through Tor.
+    pub fn host_port(&self) -> Option<(&str, u16)> {
+        match self {
+            PeerAddressType::Ws(host, port) | PeerAddressType::Wss(host, port) => Some((host.as_str(), *port)),
+            PeerAddressType::Dumb | PeerAddressType::Rtc | PeerAddressType::RtcSignaled(_) | PeerAddressType::Onion(_, _) => None,
+        }
+    }
+
+    /// The tag that identifies this type on the wire and in the signature data.
+    fn tag(&self) -> u8 {
+        match self {
+            PeerAddressType::RtcSignaled(_) => RTC_SIGNALED_TAG,
+            ty => ty.protocol() as u8,
+        }
+    }
+
+    /// Reads the type-specific part of an address with the given tag.
+    fn deserialize_with_tag<R: ReadBytesExt>(tag: u8, reader: &mut R) -> Result<Self, SerializingError> {
+        if tag == RTC_SIGNALED_TAG {
+            return Ok(PeerAddressType::RtcSignaled(Deserialize::deserialize(reader)?));
+        }
+        let protocol: Protocol = Deserialize::deserialize(&mut &[tag][..])?;
+        Ok(match protocol {
+            Protocol::Dumb => PeerAddressType::Dumb,
+            Protocol::Ws => PeerAddressType::Ws(deserialize_host(reader, MAX_HOST_LENGTH)?, Deserialize::deserialize(reader)?),
+            Protocol::Wss => PeerAddressType::Wss(deserialize_host(reader, MAX_HOST_LENGTH)?, Deserialize::deserialize(reader)?),
+            Protocol::Rtc => PeerAddressType::Rtc,
+            Protocol::Onion => PeerAddressType::Onion(deserialize_onion_host(reader)?, Deserialize::deserialize(reader)?),
+        })
+    }
+}
+
+/// Maximum length of a host name in a `PeerAddress`, the DNS limit.
+pub const MAX_HOST_LENGTH: usize = 255;
+
+/// Reads a `u8` length-prefixed host name, rejecting it before allocating if the declared length
+/// exceeds `max_len`.
+fn deserialize_host<R: ReadBytesExt>(reader: &mut R, max_len: usize) -> Result<String, SerializingError> {
+    let len: u8 = Deserialize::deserialize(reader)?;
+    let len = len as usize;
+    if len > max_len {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Host name too long").into());
+    }
+    let mut bytes = vec![0u8; len];
+    io::Read::read_exact(reader, &mut bytes)?;
+    String::from_utf8(bytes).or(Err(SerializingError::InvalidEncoding))
+}
+
+/// Length of a Tor v3 onion address: 56 base32 characters followed by `.onion`.
+pub const ONION_HOST_LENGTH: usize = 56 + 6;
+
+fn is_valid_onion_host(host: &str) -> bool {
+    host.len() == ONION_HOST_LENGTH
+        && host.ends_with(".onion")
+        && host[..ONION_HOST_LENGTH - 6].chars().all(|c| c.is_ascii_lowercase() || ('2'..='7').contains(&c))
+}
+
+fn deserialize_onion_host<R: ReadBytesExt>(reader: &mut R) -> Result<String, SerializingError> {
+    let host = deserialize_host(reader, ONION_HOST_LENGTH)?;
+    if !is_valid_onion_host(&host) {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Invalid onion address").into());
+    }
+    Ok(host)
+}
+
+/// Memoized result of `PeerAddress::verify_signature`, set on the first verification and reset
+/// by the signing APIs. Clones start without a verdict.
+#[derive(Debug, Default)]
+struct SignatureVerdict(AtomicU8);
+
+impl SignatureVerdict {
+    const UNKNOWN: u8 = 0;
+    const VALID: u8 = 1;
+    const INVALID: u8 = 2;
+
+    fn get(&self) -> Option<bool> {
+        match self.0.load(Ordering::Acquire) {
+            SignatureVerdict::VALID => Some(true),
+            SignatureVerdict::INVALID => Some(false),
+            _ => None,
         }
     }
+
+    fn set(&self, valid: bool) {
+        let verdict = if valid { SignatureVerdict::VALID } else { SignatureVerdict::INVALID };
+        self.0.store(verdict, Ordering::Release);
+    }
+
+    fn reset(&mut self) {
+        *self.0.get_mut() = SignatureVerdict::UNKNOWN;
+    }
 }
 
+impl Clone for SignatureVerdict {
+    fn clone(&self) -> Self {
+        SignatureVerdict::default()
+    }
+}
+
+/// Reason why a peer address signature couldn't be verified, see
+/// `PeerAddress::verify_signature_detailed`.
+#[derive(Debug, Clone, PartialEq, Eq, Fail)]
+pub enum SignatureError {
+    #[fail(display = "Peer address is not signed")]
+    Missing,
+    #[fail(display = "Invalid peer address signature: {}", _0)]
+    Invalid(#[cause] KeysError),
+}
+
+/// A signed peer address.
+///
+/// The signed fields (see `get_signature_data`) must not be changed after the signature has been
+/// verified, since `verify_signature` memoizes its verdict. Re-sign the address with `sign`
+/// instead, or modify a clone.
 #[derive(Debug, Clone)]
 pub struct PeerAddress {
     pub ty: PeerAddressType,
@@ -44,12 +173,13 @@ pub struct PeerAddress {
     pub distance: u8,
     pub signature: Option<Signature>,
     pub peer_id: PeerId,
+    signature_verdict: SignatureVerdict,
 }
 
 impl Serialize for PeerAddress {
     fn serialize<W: WriteBytesExt>(&self, writer: &mut W) -> Result<usize, SerializingError> {
         let mut size = 0;
-        size += self.ty.protocol().serialize(writer)?;
+        size += self.ty.tag().serialize(writer)?;
         size += self.services.serialize(writer)?;
         size += self.timestamp.serialize(writer)?;
         size += self.net_address.serialize(writer)?;
@@ -64,14 +194,16 @@ impl Serialize for PeerAddress {
             PeerAddressType::Dumb => 0,
             PeerAddressType::Ws(host, port) => host.serialize::<u8, W>(writer)? + port.serialize(writer)?,
             PeerAddressType::Wss(host, port) => host.serialize::<u8, W>(writer)? + port.serialize(writer)?,
-            PeerAddressType::Rtc => 0
+            PeerAddressType::Rtc => 0,
+            PeerAddressType::Onion(host, port) => host.serialize::<u8, W>(writer)? + port.serialize(writer)?,
+            PeerAddressType::RtcSignaled(signaling_id) => signaling_id.serialize(writer)?,
         };
         Ok(size)
     }
 
     fn serialized_size(&self) -> usize {
         let mut size = 0;
-        size += self.ty.protocol().serialized_size();
+        size += self.ty.tag().serialized_size();
         size += self.services.serialized_size();
         size += self.timestamp.serialized_size();
         size += self.net_address.serialized_size();
@@ -82,7 +214,9 @@ impl Serialize for PeerAddress {
             PeerAddressType::Dumb => 0,
             PeerAddressType::Ws(host, port) => host.serialized_size::<u8>() + port.serialized_size(),
             PeerAddressType::Wss(host, port) => host.serialized_size::<u8>() + port.serialized_size(),
-            PeerAddressType::Rtc => 0
+            PeerAddressType::Rtc => 0,
+            PeerAddressType::Onion(host, port) => host.serialized_size::<u8>() + port.serialized_size(),
+            PeerAddressType::RtcSignaled(signaling_id) => signaling_id.serialized_size(),
         };
         size
     }
@@ -90,30 +224,126 @@ impl Serialize for PeerAddress {
 
 impl Deserialize for PeerAddress {
     fn deserialize<R: ReadBytesExt>(reader: &mut R) -> Result<Self, SerializingError> {
-        let protocol: Protocol = Deserialize::deserialize(reader)?;
+        let tag: u8 = Deserialize::deserialize(reader)?;
         let services: ServiceFlags = Deserialize::deserialize(reader)?;
         let timestamp: u64 = Deserialize::deserialize(reader)?;
         let net_address: NetAddress = Deserialize::deserialize(reader)?;
         let public_key: PublicKey = Deserialize::deserialize(reader)?;
         let distance: u8 = Deserialize::deserialize(reader)?;
         let signature: Signature = Deserialize::deserialize(reader)?;
-        let type_special: PeerAddressType = match protocol {
-            Protocol::Dumb => PeerAddressType::Dumb,
-            Protocol::Ws => PeerAddressType::Ws(DeserializeWithLength::deserialize::<u8, R>(reader)?, Deserialize::deserialize(reader)?),
-            Protocol::Wss => PeerAddressType::Wss(DeserializeWithLength::deserialize::<u8, R>(reader)?, Deserialize::deserialize(reader)?),
-            Protocol::Rtc => PeerAddressType::Rtc
-        };
-        let peer_id = PeerId::from(&public_key);
-        Ok(PeerAddress{ ty: type_special, services, timestamp, net_address, public_key, distance, signature: Some(signature), peer_id})
+        let type_special = PeerAddressType::deserialize_with_tag(tag, reader)?;
+        Ok(PeerAddress::new(type_special, services, timestamp, net_address, public_key, distance, Some(signature)))
     }
 }
 
 impl PeerAddress {
+    /// Upper bound for the serialized size of a peer address with the given protocol. Dumb and
+    /// RTC addresses have a fixed size (the latter including the signaling peer ID of
+    /// `RtcSignaled`), while the size of the others depends on the length of the host name, which
+    /// is at most `MAX_HOST_LENGTH` (or `ONION_HOST_LENGTH` for onion services) and is checked
+    /// before it is read.
+    pub fn max_serialized_size(protocol: Protocol) -> usize {
+        let host_size = match protocol {
+            Protocol::Dumb => 0,
+            Protocol::Rtc => PeerId::SIZE,
+            Protocol::Ws | Protocol::Wss => 1 + MAX_HOST_LENGTH + 0u16.serialized_size(),
+            Protocol::Onion => 1 + ONION_HOST_LENGTH + 0u16.serialized_size(),
+        };
+        protocol.serialized_size()
+            + ServiceFlags::NONE.serialized_size()
+            + 0u64.serialized_size()
+            + NetAddress::IPv6(Ipv6Addr::UNSPECIFIED).serialized_size()
+            + PublicKey::SIZE
+            + 0u8.serialized_size()
+            + Signature::SIZE
+            + host_size
+    }
+
+    /// Creates a peer address. The peer ID is derived from `public_key`.
+    pub fn new(ty: PeerAddressType, services: ServiceFlags, timestamp: u64, net_address: NetAddress, public_key: PublicKey, distance: u8, signature: Option<Signature>) -> Self {
+        PeerAddress {
+            ty,
+            services,
+            timestamp,
+            net_address,
+            public_key,
+            distance,
+            signature,
+            peer_id: PeerId::from(&public_key),
+            signature_verdict: SignatureVerdict::default(),
+        }
+    }
+
+    /// Creates a peer address and signs it with `key_pair`.
+    pub fn new_signed(ty: PeerAddressType, services: ServiceFlags, timestamp: u64, net_address: NetAddress, key_pair: &KeyPair, distance: u8) -> Self {
+        let mut peer_address = PeerAddress::new(ty, services, timestamp, net_address, key_pair.public, distance, None);
+        peer_address.sign(key_pair);
+        peer_address
+    }
+
+    /// Signs this address with `key_pair`, which must be the key pair this address belongs to.
+    /// This must be called after changing signed fields, and forgets the verdict of previous
+    /// signature verifications.
+    pub fn sign(&mut self, key_pair: &KeyPair) {
+        debug_assert_eq!(key_pair.public, self.public_key, "Key pair doesn't match peer address");
+        self.signature = Some(key_pair.sign(&self.get_signature_data()));
+        self.signature_verdict.reset();
+    }
+
+    /// Verifies the signature of this address. The verdict is memoized, so repeated calls don't
+    /// verify the signature again.
     pub fn verify_signature(&self) -> bool {
-        if let Some(signature) = &self.signature {
-            return self.public_key.verify(signature, self.get_signature_data().as_slice());
+        if self.signature.is_none() {
+            return false;
         }
-        false
+        if let Some(valid) = self.signature_verdict.get() {
+            return valid;
+        }
+
+        let valid = self.verify_signature_detailed().is_ok();
+        self.signature_verdict.set(valid);
+        valid
+    }
+
+    /// Like `verify_signature`, but tells why verification failed. Unlike `verify_signature`,
+    /// this always verifies the signature and doesn't use the memoized verdict.
+    ///
+    /// The public key is decompressed, and thereby checked to be a valid point, when the address
+    /// is deserialized, so addresses with malformed keys are rejected before they get here.
+    /// Signatures that are malformed or don't match the key are both reported as
+    /// `SignatureError::Invalid`, with the underlying error telling them apart.
+    pub fn verify_signature_detailed(&self) -> Result<(), SignatureError> {
+        let signature = self.signature.as_ref().ok_or(SignatureError::Missing)?;
+        self.public_key.try_verify(signature, self.get_signature_data().as_slice())
+            .map_err(SignatureError::Invalid)
+    }
+
+    /// Returns a copy of this address with `net_address` replaced, e.g. after learning that our
+    /// public address differs from the advertised one because we are behind a NAT.
+    ///
+    /// The net address is not part of the signature data: it is the address a peer was observed
+    /// at, which relaying peers fill in themselves. So replacing it doesn't invalidate the
+    /// signature. The timestamp is still bumped (to now, but at least by one), so that peers
+    /// prefer the corrected address over the one they already know, and the address is re-signed
+    /// with `key_pair`, which must be the key pair this address belongs to.
+    pub fn with_net_address(&self, net_address: NetAddress, key_pair: &KeyPair) -> PeerAddress {
+        let now_ms = SystemTime::now().duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis() as u64)
+            .unwrap_or(0);
+
+        let mut peer_address = self.clone();
+        peer_address.net_address = net_address;
+        peer_address.timestamp = now_ms.max(self.timestamp + 1);
+        peer_address.sign(key_pair);
+        peer_address
+    }
+
+    /// Parses a peer URI like `wss://host:port/<peer_id>` or `dumb://<peer_id>`.
+    ///
+    /// A URI doesn't contain services, timestamp or signature, so this only returns the parts
+    /// that identify the peer and how to connect to it.
+    pub fn from_uri(uri: &str) -> Result<PeerUri, PeerUriError> {
+        PeerUri::from_str(uri)
     }
 
     pub fn as_uri(&self) -> PeerUri {
@@ -121,6 +351,12 @@ impl PeerAddress {
         PeerUri::from(self.clone())
     }
 
+    /// Returns the textual form of a seed address used in seed lists and config files,
+    /// `ws(s)://host:port/<public_key>`, which `from_seed_string` parses back. Unlike `as_uri`,
+    /// this contains the public key instead of the peer ID, since connecting to a seed requires
+    /// it. Signed seed lists are verified against this form, so it must not change.
+    ///
+    /// Returns `None` for addresses that aren't seeds or aren't reachable over WebSocket.
     pub fn to_seed_string(&self) -> Option<String> {
         // This function should only be called on seed nodes
         if !self.is_seed() {
@@ -135,16 +371,29 @@ impl PeerAddress {
         }
     }
 
+    /// Parses a seed address in the form produced by `to_seed_string`. The port is mandatory,
+    /// so that the result round-trips exactly.
+    pub fn from_seed_string(s: &str) -> Result<PeerAddress, PeerUriError> {
+        let uri = PeerUri::from_str(s)?;
+        if uri.port().is_none() {
+            return Err(PeerUriError::MissingPort);
+        }
+        uri.as_seed_peer_address()
+    }
+
     pub fn get_signature_data(&self) -> Vec<u8> {
-        let mut res: Vec<u8> = (self.ty.protocol() as u8).serialize_to_vec();
+        let mut res: Vec<u8> = self.ty.tag().serialize_to_vec();
         res.append(&mut self.services.serialize_to_vec());
         res.append(&mut self.timestamp.serialize_to_vec());
 
         match &self.ty {
-            PeerAddressType::Ws(host, port) | PeerAddressType::Wss(host, port) => {
+            PeerAddressType::Ws(host, port) | PeerAddressType::Wss(host, port) | PeerAddressType::Onion(host, port) => {
                 res.append(&mut host.serialize_to_vec::<u8>());
                 res.append(&mut port.serialize_to_vec());
             }
+            PeerAddressType::RtcSignaled(signaling_id) => {
+                res.append(&mut signaling_id.serialize_to_vec());
+            }
             _ => {}
         };
 
@@ -155,6 +404,31 @@ impl PeerAddress {
         self.timestamp == 0
     }
 
+    /// Age of this address in milliseconds at `now_ms`, or `None` if its timestamp lies in the
+    /// future.
+    pub fn age(&self, now_ms: u64) -> Option<u64> {
+        now_ms.checked_sub(self.timestamp)
+    }
+
+    /// Whether this address is older than `max_age_ms` at `now_ms`. Like `exceeds_age`, seed
+    /// addresses and addresses with a timestamp in the future never expire.
+    pub fn is_expired(&self, now_ms: u64, max_age_ms: u64) -> bool {
+        if self.is_seed() {
+            return false;
+        }
+        self.age(now_ms).map(|age| age > max_age_ms).unwrap_or(false)
+    }
+
+    /// Whether this address should be accepted into the address book at `now_ms`: its signature
+    /// must be valid, it must not be older than `max_age_ms`, and its timestamp must not lie more
+    /// than `MAX_CLOCK_SKEW` in the future. Unsigned (e.g. seed) addresses are never acceptable.
+    pub fn is_acceptable(&self, now_ms: u64, max_age_ms: u64) -> bool {
+        let max_timestamp = now_ms.saturating_add(MAX_CLOCK_SKEW.as_millis() as u64);
+        self.timestamp <= max_timestamp
+            && !self.is_expired(now_ms, max_age_ms)
+            && self.verify_signature()
+    }
+
     pub fn exceeds_age(&self) -> bool {
         if self.is_seed() {
             return false;
@@ -167,12 +441,19 @@ impl PeerAddress {
                 (Some(age), Protocol::Wss) =>  return age > MAX_AGE_WEBSOCKET,
                 (Some(age), Protocol::Rtc) =>  return age > MAX_AGE_WEBRTC,
                 (Some(age), Protocol::Dumb) =>  return age > MAX_AGE_DUMB,
+                (Some(age), Protocol::Onion) =>  return age > MAX_AGE_WEBSOCKET,
                 (None, _) => return false,
             }
         }
         false
     }
 
+    /// Whether the net address of this peer is globally routable, see `NetAddress::is_global`.
+    /// Peer addresses that aren't shouldn't be relayed to other peers.
+    pub fn is_routable(&self) -> bool {
+        self.net_address.is_global()
+    }
+
     pub fn is_globally_reachable(&self, legacy_mode: bool) -> bool {
         match &self.ty {
             PeerAddressType::Ws(host, _) => {
@@ -217,9 +498,31 @@ impl PeerAddress {
 
     pub fn protocol(&self) -> Protocol { self.ty.protocol() }
 
+    /// See `PeerAddressType::host_port`.
+    pub fn host_port(&self) -> Option<(&str, u16)> { self.ty.host_port() }
+
+    /// Whether this address uses an encrypted protocol, see `Protocol::is_secure`.
+    pub fn is_secure(&self) -> bool { self.protocol().is_secure() }
+
     pub fn peer_id(&self) -> &PeerId { &self.peer_id }
+
+    pub fn services(&self) -> ServiceFlags { self.services }
+
+    /// Whether the peer advertises all services in `flags`.
+    pub fn provides(&self, flags: ServiceFlags) -> bool { self.services.contains(flags) }
+
+    pub fn timestamp(&self) -> u64 { self.timestamp }
+
+    pub fn net_address(&self) -> &NetAddress { &self.net_address }
+
+    pub fn public_key(&self) -> &PublicKey { &self.public_key }
+
+    pub fn distance(&self) -> u8 { self.distance }
 }
 
+/// Two peer addresses are equal if they have the same protocol, public key and peer ID. All other
+/// fields (`services`, `timestamp`, `net_address`, `distance`, `signature` and the host and port
+/// of websocket addresses) are ignored, so updated versions of an address compare equal.
 impl PartialEq for PeerAddress {
     fn eq(&self, other: &PeerAddress) -> bool {
         // We consider peer addresses to be equal if the public key or peer id is not known on one of them:
@@ -237,14 +540,16 @@ impl PartialEq for PeerAddress {
 
 impl Eq for PeerAddress {}
 
+/// Consistent with `PartialEq`: only the protocol and peer ID are hashed.
 impl Hash for PeerAddress {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        let peer_id: String = ::hex::encode(&self.peer_id.0);
+        let peer_id = self.peer_id.to_hex();
         let peer_id_uri = match self.ty {
             PeerAddressType::Dumb => format!("dumb:///{}", peer_id),
             PeerAddressType::Ws(_, _) => format!("ws:///{}", peer_id),
             PeerAddressType::Wss(_, _) => format!("wss:///{}", peer_id),
-            PeerAddressType::Rtc => format!("rtc:///{}", peer_id)
+            PeerAddressType::Rtc | PeerAddressType::RtcSignaled(_) => format!("rtc:///{}", peer_id),
+            PeerAddressType::Onion(_, _) => format!("onion:///{}", peer_id),
         };
         peer_id_uri.hash(state);
     }
@@ -256,15 +561,21 @@ impl fmt::Display for PeerAddress {
     }
 }
 
+/// Parses a seed peer address from a URI of the form `ws(s)://host:port/<public_key>`, i.e. the
+/// format produced by `to_seed_string`. Note that `Display` writes the peer ID instead of the
+/// public key, so its output can't be parsed back into a `PeerAddress`.
+impl FromStr for PeerAddress {
+    type Err = PeerUriError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        PeerUri::from_str(s)?.as_seed_peer_address()
+    }
+}
+
 impl Deserialize for PeerAddressType {
     fn deserialize<R: ReadBytesExt>(reader: &mut R) -> Result<Self, SerializingError> {
-        let protocol: Protocol = Deserialize::deserialize(reader)?;
-        match protocol {
-            Protocol::Dumb => Ok(PeerAddressType::Dumb),
-            Protocol::Ws => Ok(PeerAddressType::Ws(DeserializeWithLength::deserialize::<u8, R>(reader)?, Deserialize::deserialize(reader)?)),
-            Protocol::Wss => Ok(PeerAddressType::Wss(DeserializeWithLength::deserialize::<u8, R>(reader)?, Deserialize::deserialize(reader)?)),
-            Protocol::Rtc => Ok(PeerAddressType::Rtc)
-        }
+        let tag: u8 = Deserialize::deserialize(reader)?;
+        PeerAddressType::deserialize_with_tag(tag, reader)
     }
 }
 
@@ -274,7 +585,9 @@ impl Serialize for PeerAddressType {
             PeerAddressType::Dumb => Protocol::Dumb.serialize(writer)?,
             PeerAddressType::Ws(host, port) => Protocol::Ws.serialize(writer)? + host.serialize::<u8, W>(writer)? + port.serialize(writer)?,
             PeerAddressType::Wss(host, port) => Protocol::Wss.serialize(writer)? + host.serialize::<u8, W>(writer)? + port.serialize(writer)?,
-            PeerAddressType::Rtc => Protocol::Rtc.serialize(writer)?
+            PeerAddressType::Rtc => Protocol::Rtc.serialize(writer)?,
+            PeerAddressType::Onion(host, port) => Protocol::Onion.serialize(writer)? + host.serialize::<u8, W>(writer)? + port.serialize(writer)?,
+            PeerAddressType::RtcSignaled(signaling_id) => RTC_SIGNALED_TAG.serialize(writer)? + signaling_id.serialize(writer)?,
         })
     }
 
@@ -282,6 +595,8 @@ impl Serialize for PeerAddressType {
         Protocol::Dumb.serialized_size() + match self {
             PeerAddressType::Ws(host, port) => host.serialized_size::<u8>() + port.serialized_size(),
             PeerAddressType::Wss(host, port) => host.serialized_size::<u8>() + port.serialized_size(),
+            PeerAddressType::Onion(host, port) => host.serialized_size::<u8>() + port.serialized_size(),
+            PeerAddressType::RtcSignaled(signaling_id) => signaling_id.serialized_size(),
             _ => 0
         }
     }
@@ -290,3 +605,5 @@ impl Serialize for PeerAddressType {
 pub const MAX_AGE_WEBSOCKET: Duration = Duration::from_secs(60 * 30); // 30 minutes
 pub const MAX_AGE_WEBRTC: Duration = Duration::from_secs(60 * 15); // 15 minutes
 pub const MAX_AGE_DUMB: Duration = Duration::from_secs(60); // 1 minute
+/// How far in the future the timestamp of an address may be, see `PeerAddress::is_acceptable`
+pub const MAX_CLOCK_SKEW: Duration = Duration::from_secs(60 * 10); // 10 minutes
@@ -8,6 +8,7 @@ use hex::{FromHex, FromHexError};
 use keys::PublicKey;
 
 use crate::address::{NetAddress, PeerAddress, PeerAddressType, PeerId};
+use crate::address::net_address::split_zone_id;
 use crate::protocol::Protocol;
 use crate::services::ServiceFlags;
 
@@ -31,6 +32,8 @@ pub enum PeerUriError {
     UnexpectedFragment,
     #[fail(display = "Unexpected port number")]
     UnexpectedPort,
+    #[fail(display = "Port is missing")]
+    MissingPort,
     #[fail(display = "Unexpected path segment")]
     UnexpectedPath,
     #[fail(display = "Too many path segments")]
@@ -66,29 +69,22 @@ impl From<keys::ParseError> for PeerUriError {
 impl FromStr for Protocol {
     type Err = PeerUriError;
 
+    /// Parses a URI scheme, ignoring case.
     fn from_str(s: &str) -> Result<Protocol, PeerUriError> {
-        match s {
-            "dumb" => Ok(Protocol::Dumb),
-            "ws" => Ok(Protocol::Ws),
-            "wss" => Ok(Protocol::Wss),
-            "rtc" => Ok(Protocol::Rtc),
-            _ => Err(PeerUriError::UnknownProtocol)
-        }
+        [Protocol::Dumb, Protocol::Ws, Protocol::Wss, Protocol::Rtc, Protocol::Onion].iter()
+            .find(|protocol| protocol.scheme().eq_ignore_ascii_case(s))
+            .cloned()
+            .ok_or(PeerUriError::UnknownProtocol)
     }
 }
 
 impl fmt::Display for Protocol {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", match self {
-            Protocol::Dumb => "dumb",
-            Protocol::Ws => "ws",
-            Protocol::Wss => "wss",
-            Protocol::Rtc => "rtc",
-        })
+        write!(f, "{}", self.scheme())
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct PeerUri {
     protocol: Protocol,
     hostname: Option<String>,
@@ -100,21 +96,57 @@ pub struct PeerUri {
 impl<'a> FromStr for PeerUri {
     type Err = PeerUriError;
 
+    /// Besides the usual URI syntax, this accepts IPv6 hosts with a zone identifier, like
+    /// `ws://[fe80::1%25eth0]:8443`, which are kept in the host name (as `[fe80::1%eth0]`).
     fn from_str(s: &str) -> Result<Self, PeerUriError> {
-        let url = Url::parse(s)?;
-        Self::from_url(url)
+        let (s, zone_id) = strip_zone_id(s);
+        let url = Url::parse(&s)?;
+        let mut uri = Self::from_url(url)?;
+        if let (Some(zone_id), Some(hostname)) = (zone_id, uri.hostname.as_mut()) {
+            if hostname.ends_with(']') {
+                hostname.insert_str(hostname.len() - 1, &format!("%{}", zone_id));
+            }
+        }
+        Ok(uri)
+    }
+}
+
+/// Removes the zone identifier from an IPv6 host in `uri`, since `Url` doesn't support them,
+/// and returns it separately. As per RFC 6874 the `%` should be encoded as `%25`, but a plain `%`
+/// is accepted as well.
+fn strip_zone_id(uri: &str) -> (String, Option<String>) {
+    let host = uri.find('[')
+        .and_then(|start| uri[start..].find(']').map(|len| (start + 1, start + len)));
+    if let Some((start, end)) = host {
+        if let (addr, Some(zone_id)) = split_zone_id(&uri[start..end]) {
+            let zone_id = match zone_id.get(..2) {
+                Some("25") => &zone_id[2..],
+                _ => zone_id,
+            };
+            if !zone_id.is_empty() {
+                let stripped = format!("{}{}{}", &uri[..start], addr, &uri[end..]);
+                return (stripped, Some(String::from(zone_id)));
+            }
+        }
     }
+    (String::from(uri), None)
 }
 
 impl<'a> fmt::Display for PeerUri {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self.protocol {
             Protocol::Dumb | Protocol::Rtc => {
-                write!(f, "{}://{}", self.protocol, self.peer_id()
+                write!(f, "{}://", self.protocol)?;
+                // WebRTC peers reached through a signaling peer have its ID as host.
+                if let Some(signaling_id) = &self.hostname {
+                    write!(f, "{}/", signaling_id)?;
+                }
+                write!(f, "{}", self.peer_id()
                     .expect("No peer ID for dumb/rtc URI"))?;
             },
-            Protocol::Ws | Protocol::Wss => {
-                write!(f, "{}://{}", self.protocol, self.hostname.as_ref().unwrap())?;
+            Protocol::Ws | Protocol::Wss | Protocol::Onion => {
+                // Encode the `%` of IPv6 zone identifiers, see RFC 6874.
+                write!(f, "{}://{}", self.protocol, self.hostname.as_ref().unwrap().replace('%', "%25"))?;
                 self.port.map(|p| write!(f, ":{}", p)).transpose()?;
                 self.peer_id().or_else(|| self.public_key()).map(|p| write!(f, "/{}", p)).transpose()?;
             }
@@ -146,7 +178,8 @@ impl PeerUri {
         // or None if there was no path segments at all. If there are multiple segments, returns
         // with an error.
         //
-        // For Dumb and Rtc this must be None (checked later). For Ws and Wss this is the peer_id.
+        // For Dumb and Rtc this is the peer_id if it isn't given as host. For Ws and Wss this is
+        // the peer_id or public key.
         let path_segment = url.path_segments()
             .and_then(|segments| {
                 let segments = segments.collect::<Vec<&str>>();
@@ -163,18 +196,29 @@ impl PeerUri {
         // Take appropriate parts of URI to construct the PeerUri
         match protocol {
             Protocol::Dumb | Protocol::Rtc => {
-                let peer_id = String::from(url.host_str().ok_or_else(|| PeerUriError::MissingPeerId)?);
                 if url.port().is_some() { return Err(PeerUriError::UnexpectedPort) }
-                if path_segment.is_some() { return Err(PeerUriError::UnexpectedPath) }
+                // The peer ID is either the host (`dumb://<peer_id>`) or, in the form without
+                // authority, the only path segment (`dumb:///<peer_id>`). WebRTC peers that are
+                // reached through a signaling peer have its ID as host (`rtc://<signaling_id>/<peer_id>`).
+                let (signaling_id, peer_id) = match (url.host_str().filter(|host| !host.is_empty()), path_segment) {
+                    (Some(host), None) => (None, String::from(host)),
+                    (None, Some(peer_id)) => (None, peer_id),
+                    (Some(host), Some(peer_id)) if protocol == Protocol::Rtc => {
+                        PeerId::from_str(host).map_err(|_| PeerUriError::InvalidPeerId)?;
+                        (Some(String::from(host)), peer_id)
+                    },
+                    (Some(_), Some(_)) => return Err(PeerUriError::UnexpectedPath),
+                    (None, None) => return Err(PeerUriError::MissingPeerId),
+                };
                 Ok(PeerUri {
                     protocol,
-                    hostname: None,
+                    hostname: signaling_id,
                     port: None,
                     peer_id: Some(peer_id), // For dumb or Rtc this is always the peer ID
                     public_key: None
                 })
             },
-            Protocol::Ws | Protocol::Wss => {
+            Protocol::Ws | Protocol::Wss | Protocol::Onion => {
                 let host = String::from(url.host_str().ok_or_else(|| PeerUriError::MissingHostname)?);
                 let (peer_id, public_key) = match path_segment {
                     Some(ref peer_id) if peer_id.len() == 2 * PeerId::SIZE => (path_segment, None),
@@ -199,6 +243,33 @@ impl PeerUri {
     pub fn peer_id(&self) -> Option<&String> { self.peer_id.as_ref() }
     pub fn public_key(&self) -> Option<&String> { self.public_key.as_ref() }
 
+    /// The port of this URI, or the default port of its protocol if none is given.
+    pub fn port_or_default(&self) -> Option<u16> { self.port.or_else(|| self.protocol.default_port()) }
+
+    /// Converts this URI into the corresponding `PeerAddressType`. A missing port is replaced by
+    /// the protocol's default port, which fails for protocols without one (i.e. onion). WebRTC URIs
+    /// with a signaling peer are converted into `PeerAddressType::RtcSignaled`.
+    pub fn to_peer_address_type(&self) -> Result<PeerAddressType, PeerUriError> {
+        match self.protocol {
+            Protocol::Dumb => Ok(PeerAddressType::Dumb),
+            Protocol::Rtc => match &self.hostname {
+                Some(signaling_id) => PeerId::from_str(signaling_id)
+                    .map(PeerAddressType::RtcSignaled)
+                    .map_err(|_| PeerUriError::InvalidPeerId),
+                None => Ok(PeerAddressType::Rtc),
+            },
+            Protocol::Ws | Protocol::Wss | Protocol::Onion => {
+                let hostname = self.hostname.clone().ok_or(PeerUriError::MissingHostname)?;
+                let port = self.port_or_default().ok_or(PeerUriError::MissingPort)?;
+                Ok(match self.protocol {
+                    Protocol::Ws => PeerAddressType::Ws(hostname, port),
+                    Protocol::Wss => PeerAddressType::Wss(hostname, port),
+                    _ => PeerAddressType::Onion(hostname, port),
+                })
+            }
+        }
+    }
+
     pub fn as_seed_peer_address(&self) -> Result<PeerAddress, PeerUriError> {
         // TODO: May be we want to allow seed nodes without public key?
         if self.public_key().is_none() {
@@ -211,26 +282,24 @@ impl PeerUri {
         };
 
         match self.protocol() {
-            Protocol::Wss => Ok(PeerAddress {
-                ty: PeerAddressType::Wss(self.hostname().expect("Mandatory for Wss").to_string(), self.port().unwrap_or(443)),
-                services: ServiceFlags::FULL,
-                timestamp: 0,
-                net_address: NetAddress::Unspecified,
+            Protocol::Wss => Ok(PeerAddress::new(
+                PeerAddressType::Wss(self.hostname().expect("Mandatory for Wss").to_string(), self.port_or_default().expect("Protocol has a default port")),
+                ServiceFlags::FULL,
+                0,
+                NetAddress::Unspecified,
                 public_key,
-                distance: 0,
-                signature: None,
-                peer_id: PeerId::from(&public_key),
-            }),
-            Protocol::Ws => Ok(PeerAddress {
-                ty: PeerAddressType::Ws(self.hostname().expect("Mandatory for Ws").to_string(), self.port().unwrap_or(80)),
-                services: ServiceFlags::FULL,
-                timestamp: 0,
-                net_address: NetAddress::Unspecified,
+                0,
+                None,
+            )),
+            Protocol::Ws => Ok(PeerAddress::new(
+                PeerAddressType::Ws(self.hostname().expect("Mandatory for Ws").to_string(), self.port_or_default().expect("Protocol has a default port")),
+                ServiceFlags::FULL,
+                0,
+                NetAddress::Unspecified,
                 public_key,
-                distance: 0,
-                signature: None,
-                peer_id: PeerId::from(&public_key),
-            }),
+                0,
+                None,
+            )),
             _ => Err(PeerUriError::SeedNodeWithInvalidProtocol),
         }
     }
@@ -245,7 +314,10 @@ impl From<PeerAddress> for PeerUri {
             PeerAddressType::Dumb | PeerAddressType::Rtc => {
                 PeerUri { protocol, peer_id, hostname: None, port: None, public_key: None }
             },
-            PeerAddressType::Ws(host, port) | PeerAddressType::Wss(host, port) => {
+            PeerAddressType::RtcSignaled(signaling_id) => {
+                PeerUri { protocol, peer_id, hostname: Some(signaling_id.to_hex()), port: None, public_key: None }
+            },
+            PeerAddressType::Ws(host, port) | PeerAddressType::Wss(host, port) | PeerAddressType::Onion(host, port) => {
                 PeerUri { protocol, peer_id, hostname: Some(host), port: Some(port), public_key: None }
             }
         }
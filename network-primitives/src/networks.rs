@@ -2,7 +2,6 @@ pub use primitives::networks::NetworkId;
 use crate::address::net_address::NetAddress;
 use crate::address::peer_address::PeerAddress;
 use crate::address::peer_address::PeerAddressType;
-use crate::address::PeerId;
 use crate::services::ServiceFlags;
 use crate::address::seed_list::SeedList;
 use beserial::Deserialize;
@@ -175,12 +174,12 @@ lazy_static! {
 
 fn create_seed_peer_addr(url: &str, port: u16, pubkey_hex: &str) -> PeerAddress {
     let public_key = PublicKey::from_hex(pubkey_hex).unwrap();
-    PeerAddress { ty: PeerAddressType::Wss(url.to_string(), port), services: ServiceFlags::FULL, timestamp: 0, net_address: NetAddress::Unspecified, public_key, distance: 0, signature: None, peer_id: PeerId::from(&public_key)}
+    PeerAddress::new(PeerAddressType::Wss(url.to_string(), port), ServiceFlags::FULL, 0, NetAddress::Unspecified, public_key, 0, None)
 }
 
 fn create_seed_peer_addr_ws(url: &str, port: u16, pubkey_hex: &str) -> PeerAddress {
     let public_key = PublicKey::from_hex(pubkey_hex).unwrap();
-    PeerAddress { ty: PeerAddressType::Ws(url.to_string(), port), services: ServiceFlags::FULL, timestamp: 0, net_address: NetAddress::Unspecified, public_key, distance: 0, signature: None, peer_id: PeerId::from(&public_key)}
+    PeerAddress::new(PeerAddressType::Ws(url.to_string(), port), ServiceFlags::FULL, 0, NetAddress::Unspecified, public_key, 0, None)
 }
 
 fn create_seed_list(url_str: &str, pubkey_hex: &str) -> SeedList {
@@ -6,7 +6,8 @@ pub enum Protocol {
     Dumb = 0,
     Wss = 1,
     Rtc = 2,
-    Ws = 4
+    Ws = 4,
+    Onion = 8,
 }
 
 impl From<ProtocolFlags> for Vec<Protocol> {
@@ -24,6 +25,9 @@ impl From<ProtocolFlags> for Vec<Protocol> {
         if flags.contains(ProtocolFlags::WS) {
             v.push(Protocol::Ws);
         }
+        if flags.contains(ProtocolFlags::ONION) {
+            v.push(Protocol::Onion);
+        }
         v
     }
 }
@@ -35,6 +39,7 @@ bitflags! {
         const WSS   = 0b0000_0001;
         const RTC   = 0b0000_0010;
         const WS    = 0b0000_0100;
+        const ONION = 0b0000_1000;
     }
 }
 
@@ -45,6 +50,7 @@ impl From<Protocol> for ProtocolFlags {
             Protocol::Rtc => ProtocolFlags::RTC,
             Protocol::Wss => ProtocolFlags::WSS,
             Protocol::Ws => ProtocolFlags::WS,
+            Protocol::Onion => ProtocolFlags::ONION,
         }
     }
 }
@@ -60,10 +66,34 @@ impl From<Vec<Protocol>> for ProtocolFlags {
 }
 
 impl Protocol {
+    /// The URI scheme of this protocol, e.g. `wss`.
+    pub fn scheme(self) -> &'static str {
+        match self {
+            Protocol::Dumb => "dumb",
+            Protocol::Ws => "ws",
+            Protocol::Wss => "wss",
+            Protocol::Rtc => "rtc",
+            Protocol::Onion => "onion",
+        }
+    }
+
+    /// Port to use for a URI of this protocol that doesn't specify one, i.e. the conventional
+    /// port of the URI scheme. Protocols without a port (and onion services, which don't have a
+    /// conventional port) return `None`.
     pub fn default_port(self) -> Option<u16> {
         match self {
-            Protocol::Ws | Protocol::Wss => Some(8443),
-            _ => None
+            Protocol::Ws => Some(80),
+            Protocol::Wss => Some(443),
+            Protocol::Dumb | Protocol::Rtc | Protocol::Onion => None,
+        }
+    }
+
+    /// Whether connections over this protocol are encrypted. Onion services are end-to-end
+    /// encrypted by Tor.
+    pub fn is_secure(self) -> bool {
+        match self {
+            Protocol::Wss | Protocol::Onion => true,
+            Protocol::Dumb | Protocol::Ws | Protocol::Rtc => false,
         }
     }
 }
@@ -0,0 +1,99 @@
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use network_primitives::address::NetAddress;
+
+fn ipv4(s: &str) -> NetAddress {
+    NetAddress::IPv4(s.parse::<Ipv4Addr>().unwrap())
+}
+
+fn ipv6(s: &str) -> NetAddress {
+    NetAddress::IPv6(s.parse::<Ipv6Addr>().unwrap())
+}
+
+#[test]
+fn test_subnet_ipv4() {
+    let address = ipv4("192.168.123.45");
+    assert_eq!(address.subnet(32), address);
+    assert_eq!(address.subnet(24), ipv4("192.168.123.0"));
+    assert_eq!(address.subnet(20), ipv4("192.168.112.0"));
+    assert_eq!(address.subnet(16), ipv4("192.168.0.0"));
+    assert_eq!(address.subnet(0), ipv4("0.0.0.0"));
+}
+
+#[test]
+fn test_subnet_ipv6() {
+    let address = ipv6("2001:db8:abcd:12ff:1:2:3:4");
+    assert_eq!(address.subnet(128), address);
+    assert_eq!(address.subnet(64), ipv6("2001:db8:abcd:12ff::"));
+    assert_eq!(address.subnet(56), ipv6("2001:db8:abcd:1200::"));
+    assert_eq!(address.subnet(0), ipv6("::"));
+}
+
+#[test]
+fn test_is_in_same_subnet() {
+    assert!(ipv4("10.0.1.1").is_in_same_subnet(&ipv4("10.0.1.254"), 24));
+    assert!(!ipv4("10.0.1.1").is_in_same_subnet(&ipv4("10.0.2.1"), 24));
+    assert!(ipv4("10.0.1.1").is_in_same_subnet(&ipv4("10.0.2.1"), 22));
+
+    assert!(ipv6("2001:db8::1").is_in_same_subnet(&ipv6("2001:db8::ffff"), 64));
+    assert!(!ipv6("2001:db8::1").is_in_same_subnet(&ipv6("2001:db8:0:1::1"), 64));
+
+    // Different families and pseudo addresses are never in the same subnet.
+    assert!(!ipv4("0.0.0.0").is_in_same_subnet(&ipv6("::"), 0));
+    assert!(!NetAddress::Unspecified.is_in_same_subnet(&NetAddress::Unspecified, 0));
+}
+
+#[test]
+fn test_parse_zone_id() {
+    assert_eq!(NetAddress::parse_with_zone_id("fe80::1%eth0").unwrap(), (ipv6("fe80::1"), Some(String::from("eth0"))));
+    assert_eq!(NetAddress::parse_with_zone_id("fe80::1").unwrap(), (ipv6("fe80::1"), None));
+    assert_eq!(NetAddress::parse_with_zone_id("192.168.1.1").unwrap(), (ipv4("192.168.1.1"), None));
+    assert!(NetAddress::parse_with_zone_id("192.168.1.1%eth0").is_err());
+    assert!(NetAddress::parse_with_zone_id("fe80::1%").is_err());
+
+    // The zone ID is not part of the address, so it is dropped when parsed as a `NetAddress`.
+    assert_eq!("fe80::1%eth0".parse::<NetAddress>().unwrap(), ipv6("fe80::1"));
+}
+
+#[test]
+fn test_classify_addresses() {
+    let loopback = vec![ipv4("127.0.0.1"), ipv4("127.1.2.3"), ipv6("::1"), ipv6("::ffff:127.0.0.1")];
+    for address in loopback {
+        assert!(address.is_loopback(), "{:?}", address);
+        assert!(!address.is_private(), "{:?}", address);
+        assert!(!address.is_global(), "{:?}", address);
+    }
+
+    let private = vec![
+        ipv4("10.0.0.1"), ipv4("172.16.0.1"), ipv4("172.31.255.255"), ipv4("192.168.2.1"),
+        ipv4("100.64.0.1"), ipv4("169.254.1.1"),
+        ipv6("fd12:3456:789a:1::1"), ipv6("fc00::1"), ipv6("fe80::1"), ipv6("::ffff:192.168.2.1"),
+    ];
+    for address in private {
+        assert!(!address.is_loopback(), "{:?}", address);
+        assert!(address.is_private(), "{:?}", address);
+        assert!(!address.is_global(), "{:?}", address);
+    }
+
+    let global = vec![
+        ipv4("8.8.8.8"), ipv4("172.32.0.1"), ipv4("100.128.0.1"),
+        ipv6("2001:4860:4860::8888"), ipv6("::ffff:8.8.8.8"),
+    ];
+    for address in global {
+        assert!(!address.is_loopback(), "{:?}", address);
+        assert!(!address.is_private(), "{:?}", address);
+        assert!(address.is_global(), "{:?}", address);
+    }
+
+    // Special-use addresses that are neither loopback nor private aren't global either.
+    let special = vec![
+        ipv4("0.0.0.0"), ipv4("0.1.2.3"), ipv4("255.255.255.255"),
+        ipv6("::"), ipv6("ff02::1"),
+        NetAddress::Unspecified, NetAddress::Unknown,
+    ];
+    for address in special {
+        assert!(!address.is_loopback(), "{:?}", address);
+        assert!(!address.is_private(), "{:?}", address);
+        assert!(!address.is_global(), "{:?}", address);
+    }
+}
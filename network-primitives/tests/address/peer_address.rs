@@ -0,0 +1,457 @@
+use std::collections::HashSet;
+use std::str::FromStr;
+
+use beserial::{Deserialize, Serialize, SerializingError};
+use nimiq_keys::{KeyPair, PrivateKey, PublicKey, SecureGenerate};
+use network_primitives::address::{MAX_CLOCK_SKEW, MAX_HOST_LENGTH, NetAddress, PeerAddress, PeerAddressType, PeerId, ONION_HOST_LENGTH, RTC_SIGNALED_TAG, SignatureError};
+use network_primitives::protocol::Protocol;
+use network_primitives::services::ServiceFlags;
+
+const PRIVATE_KEY: &str = "9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60";
+const PUBLIC_KEY: &str = "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a";
+const PEER_ID: &str = "7849ac3049680be1ef762efe0d36e017";
+
+fn peer_address(ty: PeerAddressType) -> PeerAddress {
+    let public_key = PublicKey::from_str(PUBLIC_KEY).unwrap();
+    PeerAddress::new(ty, ServiceFlags::FULL, 0, NetAddress::Unspecified, public_key, 0, None)
+}
+
+#[test]
+fn test_as_uri() {
+    let ws = peer_address(PeerAddressType::Ws(String::from("seed-20.nimiq.com"), 8080));
+    assert_eq!(ws.as_uri().to_string(), format!("ws://seed-20.nimiq.com:8080/{}", PEER_ID));
+
+    let wss = peer_address(PeerAddressType::Wss(String::from("seed-20.nimiq.com"), 8443));
+    assert_eq!(wss.as_uri().to_string(), format!("wss://seed-20.nimiq.com:8443/{}", PEER_ID));
+
+    let dumb = peer_address(PeerAddressType::Dumb);
+    assert_eq!(dumb.as_uri().to_string(), format!("dumb://{}", PEER_ID));
+
+    let rtc = peer_address(PeerAddressType::Rtc);
+    assert_eq!(rtc.as_uri().to_string(), format!("rtc://{}", PEER_ID));
+
+    let link_local = peer_address(PeerAddressType::Ws(String::from("[fe80::1%eth0]"), 8443));
+    assert_eq!(link_local.as_uri().to_string(), format!("ws://[fe80::1%25eth0]:8443/{}", PEER_ID));
+}
+
+#[test]
+fn test_rtc_signaled() {
+    let key_pair = KeyPair::from(PrivateKey::from_str(PRIVATE_KEY).unwrap());
+    let signaling_id = PeerId::from_str("2b3f0f59334ef71ee7869b451139587f").unwrap();
+    let signaled = PeerAddress::new_signed(PeerAddressType::RtcSignaled(signaling_id.clone()), ServiceFlags::FULL, 1_565_713_920_000, NetAddress::Unspecified, &key_pair, 1);
+    assert_eq!(signaled.protocol(), Protocol::Rtc);
+    assert_eq!(signaled.as_uri().to_string(), format!("rtc://{}/{}", signaling_id, PEER_ID));
+
+    let serialized = signaled.serialize_to_vec();
+    assert_eq!(serialized[0], RTC_SIGNALED_TAG);
+    assert_eq!(serialized.len(), signaled.serialized_size());
+    let deserialized = PeerAddress::deserialize_from_vec(&serialized).unwrap();
+    assert_eq!(deserialized.ty, PeerAddressType::RtcSignaled(signaling_id.clone()));
+    assert!(deserialized.verify_signature());
+
+    // The signaling peer is signed, too.
+    let mut tampered = deserialized.clone();
+    tampered.ty = PeerAddressType::RtcSignaled(PeerId::from_str(PEER_ID).unwrap());
+    assert!(!tampered.verify_signature());
+
+    let ty = PeerAddressType::RtcSignaled(signaling_id);
+    assert_eq!(PeerAddressType::deserialize_from_vec(&ty.serialize_to_vec()).unwrap(), ty);
+}
+
+#[test]
+fn test_rtc_backward_compatibility() {
+    // Rtc addresses without a signaling peer keep their wire format: tagged with the protocol
+    // and without any type-specific data.
+    let key_pair = KeyPair::from(PrivateKey::from_str(PRIVATE_KEY).unwrap());
+    let rtc = PeerAddress::new_signed(PeerAddressType::Rtc, ServiceFlags::FULL, 1_565_713_920_000, NetAddress::Unspecified, &key_pair, 1);
+    let dumb = PeerAddress::new_signed(PeerAddressType::Dumb, ServiceFlags::FULL, 1_565_713_920_000, NetAddress::Unspecified, &key_pair, 1);
+    let serialized = rtc.serialize_to_vec();
+    assert_eq!(serialized[0], Protocol::Rtc as u8);
+    assert_eq!(serialized.len(), dumb.serialize_to_vec().len());
+    assert_eq!(rtc.get_signature_data()[0], Protocol::Rtc as u8);
+    assert_eq!(PeerAddress::deserialize_from_vec(&serialized).unwrap().ty, PeerAddressType::Rtc);
+    assert_eq!(PeerAddressType::Rtc.serialize_to_vec(), vec![Protocol::Rtc as u8]);
+
+    // Older clients only know the protocol discriminants, so the new tag isn't one of them.
+    assert!(Protocol::deserialize_from_vec(&[RTC_SIGNALED_TAG]).is_err());
+}
+
+#[test]
+fn test_parse_seed_peer_address() {
+    let peer_address: PeerAddress = format!("wss://seed-20.nimiq.com:8443/{}", PUBLIC_KEY).parse().unwrap();
+    assert_eq!(peer_address.ty, PeerAddressType::Wss(String::from("seed-20.nimiq.com"), 8443));
+    assert_eq!(peer_address.public_key, PublicKey::from_str(PUBLIC_KEY).unwrap());
+    assert_eq!(peer_address.to_seed_string(), Some(format!("wss://seed-20.nimiq.com:8443/{}", PUBLIC_KEY)));
+    assert_eq!(peer_address.to_string(), format!("wss://seed-20.nimiq.com:8443/{}", PEER_ID));
+
+    // Peer ID instead of public key
+    assert!(PeerAddress::from_str(&format!("wss://seed-20.nimiq.com:8443/{}", PEER_ID)).is_err());
+    // Unknown protocol
+    assert!(PeerAddress::from_str(&format!("foo://seed-20.nimiq.com:8443/{}", PUBLIC_KEY)).is_err());
+    // Bad port
+    assert!(PeerAddress::from_str(&format!("wss://seed-20.nimiq.com:foo/{}", PUBLIC_KEY)).is_err());
+    // Bad public key
+    assert!(PeerAddress::from_str("wss://seed-20.nimiq.com:8443/zz5a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a").is_err());
+}
+
+#[test]
+fn test_seed_string_round_trip() {
+    for seed_string in vec![
+        format!("wss://seed-20.nimiq.com:8443/{}", PUBLIC_KEY),
+        format!("ws://seed-20.nimiq.com:8080/{}", PUBLIC_KEY),
+        format!("ws://127.0.0.1:8443/{}", PUBLIC_KEY),
+    ] {
+        let seed = PeerAddress::from_seed_string(&seed_string).unwrap();
+        assert_eq!(seed.public_key, PublicKey::from_str(PUBLIC_KEY).unwrap());
+        assert_eq!(seed.to_seed_string(), Some(seed_string));
+    }
+
+    // Seeds can only be reached over WebSocket.
+    let host = format!("{}.onion", "a".repeat(52) + "2345");
+    for ty in vec![PeerAddressType::Dumb, PeerAddressType::Rtc, PeerAddressType::Onion(host.clone(), 8443)] {
+        assert_eq!(peer_address(ty).to_seed_string(), None);
+    }
+    assert!(PeerAddress::from_seed_string(&format!("dumb://{}", PUBLIC_KEY)).is_err());
+    assert!(PeerAddress::from_seed_string(&format!("rtc://{}", PUBLIC_KEY)).is_err());
+    assert!(PeerAddress::from_seed_string(&format!("onion://{}:8443/{}", host, PUBLIC_KEY)).is_err());
+
+    // Only seeds have a seed string.
+    let mut not_seed = peer_address(PeerAddressType::Wss(String::from("seed-20.nimiq.com"), 8443));
+    not_seed.timestamp = 1;
+    assert_eq!(not_seed.to_seed_string(), None);
+
+    // Malformed input
+    assert!(PeerAddress::from_seed_string(&format!("wss://seed-20.nimiq.com/{}", PUBLIC_KEY)).is_err());
+    assert!(PeerAddress::from_seed_string(&format!("wss://seed-20.nimiq.com:8443/{}", PEER_ID)).is_err());
+    assert!(PeerAddress::from_seed_string("wss://seed-20.nimiq.com:8443").is_err());
+    assert!(PeerAddress::from_seed_string(&format!("wss:seed-20.nimiq.com:8443:{}", PUBLIC_KEY)).is_err());
+    assert!(PeerAddress::from_seed_string("").is_err());
+}
+
+#[test]
+fn test_equality_ignores_transient_fields() {
+    let peer_address = peer_address(PeerAddressType::Wss(String::from("seed-20.nimiq.com"), 8443));
+    let mut updated = peer_address.clone();
+    updated.timestamp = 1_000_000;
+    updated.distance = 2;
+    updated.services = ServiceFlags::LIGHT;
+    assert_eq!(peer_address, updated);
+
+    let mut set = HashSet::new();
+    set.insert(peer_address.clone());
+    set.insert(updated);
+    assert_eq!(set.len(), 1);
+
+    let mut other_protocol = peer_address.clone();
+    other_protocol.ty = PeerAddressType::Ws(String::from("seed-20.nimiq.com"), 8443);
+    assert_ne!(peer_address, other_protocol);
+}
+
+#[test]
+fn test_peer_id_hex() {
+    let peer_id = PeerId::from(&PublicKey::from_str(PUBLIC_KEY).unwrap());
+    assert_eq!(peer_id.to_hex(), PEER_ID);
+    assert_eq!(PeerId::from_str(PEER_ID).unwrap(), peer_id);
+
+    // Valid hex of the wrong length is rejected instead of being truncated or padded.
+    assert!(PeerId::from_str(&PEER_ID[..30]).is_err());
+    assert!(PeerId::from_str(&format!("{}00", PEER_ID)).is_err());
+    assert!(PeerId::from_str(PUBLIC_KEY).is_err());
+    assert!(PeerId::from_str("zz49ac3049680be1ef762efe0d36e017").is_err());
+}
+
+#[test]
+fn test_sign_and_verify() {
+    let key_pair = KeyPair::from(PrivateKey::from_str(PRIVATE_KEY).unwrap());
+    let ty = PeerAddressType::Wss(String::from("seed-20.nimiq.com"), 8443);
+    let peer_address = PeerAddress::new_signed(ty, ServiceFlags::FULL, 1_565_713_920_000, NetAddress::Unspecified, &key_pair, 0);
+    assert_eq!(peer_address.public_key, PublicKey::from_str(PUBLIC_KEY).unwrap());
+    assert_eq!(peer_address.peer_id.to_hex(), PEER_ID);
+    assert!(peer_address.verify_signature());
+
+    // Changing a signed field invalidates the signature.
+    let mut tampered = peer_address.clone();
+    tampered.timestamp += 1;
+    assert!(!tampered.verify_signature());
+
+    // Until the address is signed again.
+    tampered.sign(&key_pair);
+    assert!(tampered.verify_signature());
+}
+
+/// The signature data must stay byte-for-byte compatible with other clients, since they verify
+/// our signatures and we verify theirs.
+#[test]
+fn test_signature_data_golden_vector() {
+    let mut wss = peer_address(PeerAddressType::Wss(String::from("seed-20.nimiq.com"), 8443));
+    wss.timestamp = 1_565_713_920_000;
+    // protocol | services | timestamp | host length | host | port
+    assert_eq!(hex::encode(wss.get_signature_data()), "01000000040000016c8bd2700011736565642d32302e6e696d69712e636f6d20fb");
+
+    // The net address and distance are not signed.
+    wss.net_address = NetAddress::from_str("93.184.216.34").unwrap();
+    wss.distance = 3;
+    assert_eq!(hex::encode(wss.get_signature_data()), "01000000040000016c8bd2700011736565642d32302e6e696d69712e636f6d20fb");
+
+    let mut dumb = peer_address(PeerAddressType::Dumb);
+    dumb.services = ServiceFlags::NANO;
+    dumb.timestamp = 1_565_713_920_000;
+    assert_eq!(hex::encode(dumb.get_signature_data()), "00000000010000016c8bd27000");
+}
+
+#[test]
+fn test_with_net_address() {
+    let key_pair = KeyPair::from(PrivateKey::from_str(PRIVATE_KEY).unwrap());
+    let ty = PeerAddressType::Wss(String::from("seed-20.nimiq.com"), 8443);
+    let peer_address = PeerAddress::new_signed(ty, ServiceFlags::FULL, 1_565_713_920_000, NetAddress::Unspecified, &key_pair, 0);
+
+    let net_address = NetAddress::from_str("93.184.216.34").unwrap();
+    let corrected = peer_address.with_net_address(net_address, &key_pair);
+    assert_eq!(corrected.net_address, net_address);
+    assert!(corrected.timestamp > peer_address.timestamp);
+    assert!(corrected.verify_signature());
+    assert_eq!(corrected, peer_address);
+
+    // The net address is not signed, so changing it alone keeps the signature valid.
+    let mut unsigned = peer_address.clone();
+    unsigned.net_address = net_address;
+    assert!(unsigned.verify_signature());
+}
+
+#[test]
+fn test_cached_signature_verdict() {
+    let key_pair = KeyPair::from(PrivateKey::from_str(PRIVATE_KEY).unwrap());
+    let other_key_pair = KeyPair::generate_default_csprng();
+    let ty = PeerAddressType::Wss(String::from("seed-20.nimiq.com"), 8443);
+    let mut peer_address = PeerAddress::new_signed(ty, ServiceFlags::FULL, 1_565_713_920_000, NetAddress::Unspecified, &key_pair, 0);
+
+    // Signed by the wrong key: the verdict is memoized and stays the same.
+    peer_address.signature = Some(other_key_pair.sign(&peer_address.get_signature_data()));
+    assert!(!peer_address.verify_signature());
+    assert!(!peer_address.verify_signature());
+    assert!(!peer_address.clone().verify_signature());
+
+    // Re-signing forgets the memoized verdict.
+    let resigned = peer_address.with_net_address(NetAddress::Unspecified, &key_pair);
+    assert!(resigned.verify_signature());
+    assert!(resigned.verify_signature());
+
+    peer_address.sign(&key_pair);
+    assert!(peer_address.verify_signature());
+}
+
+#[test]
+fn test_is_routable() {
+    let key_pair = KeyPair::from(PrivateKey::from_str(PRIVATE_KEY).unwrap());
+    let ty = PeerAddressType::Wss(String::from("seed-20.nimiq.com"), 8443);
+    let peer_address = PeerAddress::new_signed(ty, ServiceFlags::FULL, 1_565_713_920_000, NetAddress::Unspecified, &key_pair, 0);
+    assert!(!peer_address.is_routable());
+
+    for (net_address, routable) in vec![("93.184.216.34", true), ("192.168.2.1", false), ("127.0.0.1", false), ("fe80::1", false)] {
+        let with_address = peer_address.with_net_address(NetAddress::from_str(net_address).unwrap(), &key_pair);
+        assert_eq!(with_address.is_routable(), routable, "{}", net_address);
+    }
+}
+
+#[test]
+fn test_onion_address() {
+    let key_pair = KeyPair::from(PrivateKey::from_str(PRIVATE_KEY).unwrap());
+    let host = format!("{}.onion", "a".repeat(52) + "2345");
+    let ty = PeerAddressType::Onion(host.clone(), 8443);
+    let peer_address = PeerAddress::new_signed(ty, ServiceFlags::FULL, 1_565_713_920_000, NetAddress::Unspecified, &key_pair, 0);
+    assert_eq!(peer_address.as_uri().to_string(), format!("onion://{}:8443/{}", host, PEER_ID));
+
+    let serialized = peer_address.serialize_to_vec();
+    let deserialized = PeerAddress::deserialize_from_vec(&serialized).unwrap();
+    assert_eq!(deserialized.ty, peer_address.ty);
+    assert!(deserialized.verify_signature());
+
+    // Hosts that aren't v3 onion addresses are rejected.
+    let invalid = PeerAddress::new_signed(PeerAddressType::Onion(String::from("seed-20.nimiq.com"), 8443), ServiceFlags::FULL, 1_565_713_920_000, NetAddress::Unspecified, &key_pair, 0);
+    assert!(PeerAddress::deserialize_from_vec(&invalid.serialize_to_vec()).is_err());
+}
+
+#[test]
+fn test_deserialize_rejects_malformed_host() {
+    let key_pair = KeyPair::from(PrivateKey::from_str(PRIVATE_KEY).unwrap());
+    let ty = PeerAddressType::Wss(String::from("seed-20.nimiq.com"), 8443);
+    let serialized = PeerAddress::new_signed(ty, ServiceFlags::FULL, 1_565_713_920_000, NetAddress::Unspecified, &key_pair, 0).serialize_to_vec();
+    // The host is serialized last, followed by its length-prefixed bytes and the port.
+    let host_offset = serialized.len() - 2 - "seed-20.nimiq.com".len() - 1;
+
+    // Any declared host length that doesn't match the data must be rejected without panicking.
+    for len in 0..=255u8 {
+        let mut data = serialized.clone();
+        data[host_offset] = len;
+        let result = PeerAddress::deserialize_from_vec(&data);
+        if len as usize == "seed-20.nimiq.com".len() {
+            assert!(result.is_ok());
+        } else if len as usize > "seed-20.nimiq.com".len() + 2 {
+            assert!(result.is_err());
+        }
+    }
+
+    // Declaring the maximum host length with no data following is rejected.
+    let mut data = serialized[..=host_offset].to_vec();
+    data[host_offset] = 0xff;
+    assert!(PeerAddress::deserialize_from_vec(&data).is_err());
+}
+
+#[test]
+fn test_max_serialized_size() {
+    let key_pair = KeyPair::from(PrivateKey::from_str(PRIVATE_KEY).unwrap());
+    let net_address = NetAddress::from_str("2001:db8::1").unwrap();
+    let onion_host = format!("{}.onion", "a".repeat(52) + "2345");
+    let long_host = "a".repeat(MAX_HOST_LENGTH);
+    let types = vec![
+        PeerAddressType::Dumb,
+        PeerAddressType::RtcSignaled(PeerId::from_str(PEER_ID).unwrap()),
+        PeerAddressType::Ws(long_host.clone(), 8443),
+        PeerAddressType::Wss(long_host, 8443),
+        PeerAddressType::Onion(onion_host, 8443),
+    ];
+
+    // Addresses with an IPv6 net address and the longest possible host reach the maximum.
+    for ty in types {
+        let protocol = ty.protocol();
+        let signed = PeerAddress::new_signed(ty, ServiceFlags::FULL, 1_565_713_920_000, net_address, &key_pair, 0);
+        assert_eq!(signed.serialized_size(), PeerAddress::max_serialized_size(protocol));
+        assert_eq!(signed.serialize_to_vec().len(), PeerAddress::max_serialized_size(protocol));
+    }
+    let rtc = PeerAddress::new_signed(PeerAddressType::Rtc, ServiceFlags::FULL, 1_565_713_920_000, net_address, &key_pair, 0);
+    assert!(rtc.serialized_size() < PeerAddress::max_serialized_size(Protocol::Rtc));
+    assert!(PeerAddress::max_serialized_size(Protocol::Dumb) < PeerAddress::max_serialized_size(Protocol::Onion));
+    assert!(PeerAddress::max_serialized_size(Protocol::Onion) < PeerAddress::max_serialized_size(Protocol::Wss));
+
+    // Onion hosts are rejected by their declared length already.
+    let ty = PeerAddressType::Onion(format!("{}.onion", "a".repeat(56)), 8443);
+    let serialized = PeerAddress::new_signed(ty, ServiceFlags::FULL, 1_565_713_920_000, net_address, &key_pair, 0).serialize_to_vec();
+    let host_offset = serialized.len() - 2 - ONION_HOST_LENGTH - 1;
+    let mut data = serialized[..=host_offset].to_vec();
+    data[host_offset] = (ONION_HOST_LENGTH + 1) as u8;
+    data.extend_from_slice(&[b'a'; ONION_HOST_LENGTH + 1]);
+    data.extend_from_slice(&8443u16.to_be_bytes());
+    assert!(PeerAddress::deserialize_from_vec(&data).is_err());
+}
+
+#[test]
+fn test_deserialize_rejects_unknown_protocol() {
+    let key_pair = KeyPair::from(PrivateKey::from_str(PRIVATE_KEY).unwrap());
+    let ty = PeerAddressType::Wss(String::from("seed-20.nimiq.com"), 8443);
+    let serialized = PeerAddress::new_signed(ty, ServiceFlags::FULL, 1_565_713_920_000, NetAddress::Unspecified, &key_pair, 0).serialize_to_vec();
+    let known = [Protocol::Dumb, Protocol::Wss, Protocol::Rtc, Protocol::Ws, Protocol::Onion];
+
+    // The protocol is serialized first.
+    for discriminant in 0..=255u8 {
+        if known.iter().any(|protocol| *protocol as u8 == discriminant) {
+            continue;
+        }
+        assert_eq!(Protocol::deserialize_from_vec(&[discriminant]), Err(SerializingError::InvalidValue));
+        // The tag of `RtcSignaled` isn't a protocol, but a known address type.
+        if discriminant == RTC_SIGNALED_TAG {
+            continue;
+        }
+        assert_eq!(PeerAddressType::deserialize_from_vec(&[discriminant]), Err(SerializingError::InvalidValue));
+
+        let mut data = serialized.clone();
+        data[0] = discriminant;
+        assert_eq!(PeerAddress::deserialize_from_vec(&data).unwrap_err(), SerializingError::InvalidValue);
+    }
+}
+
+#[test]
+fn test_age_and_expiry() {
+    let mut peer_address = peer_address(PeerAddressType::Wss(String::from("seed-20.nimiq.com"), 8443));
+    peer_address.timestamp = 1_000;
+    assert_eq!(peer_address.age(1_000), Some(0));
+    assert_eq!(peer_address.age(1_500), Some(500));
+    assert!(!peer_address.is_expired(1_500, 500));
+    assert!(peer_address.is_expired(1_501, 500));
+
+    // Timestamps in the future have no age and don't expire.
+    assert_eq!(peer_address.age(999), None);
+    assert!(!peer_address.is_expired(999, 0));
+
+    // Seed addresses never expire.
+    peer_address.timestamp = 0;
+    assert_eq!(peer_address.age(1_000), Some(1_000));
+    assert!(!peer_address.is_expired(1_000, 0));
+}
+
+#[test]
+fn test_is_acceptable() {
+    let key_pair = KeyPair::from(PrivateKey::from_str(PRIVATE_KEY).unwrap());
+    let ty = PeerAddressType::Wss(String::from("seed-20.nimiq.com"), 8443);
+    let timestamp = 1_565_713_920_000;
+    let max_skew = MAX_CLOCK_SKEW.as_millis() as u64;
+    let signed = PeerAddress::new_signed(ty.clone(), ServiceFlags::FULL, timestamp, NetAddress::Unspecified, &key_pair, 0);
+
+    assert!(signed.is_acceptable(timestamp, 0));
+    assert!(signed.is_acceptable(timestamp + 500, 500));
+    // Expired
+    assert!(!signed.is_acceptable(timestamp + 501, 500));
+    // From the future, within and beyond the allowed clock skew
+    assert!(signed.is_acceptable(timestamp - max_skew, 500));
+    assert!(!signed.is_acceptable(timestamp - max_skew - 1, 500));
+
+    // Invalid or missing signature
+    let mut tampered = signed.clone();
+    tampered.services = ServiceFlags::NONE;
+    assert!(!tampered.is_acceptable(timestamp, 500));
+    let unsigned = peer_address(ty);
+    assert!(!unsigned.is_acceptable(timestamp, 500));
+}
+
+#[test]
+fn test_verify_signature_detailed() {
+    let key_pair = KeyPair::from(PrivateKey::from_str(PRIVATE_KEY).unwrap());
+    let ty = PeerAddressType::Wss(String::from("seed-20.nimiq.com"), 8443);
+    let signed = PeerAddress::new_signed(ty.clone(), ServiceFlags::FULL, 1_565_713_920_000, NetAddress::Unspecified, &key_pair, 0);
+    assert_eq!(signed.verify_signature_detailed(), Ok(()));
+
+    let mut tampered = signed.clone();
+    tampered.services = ServiceFlags::NONE;
+    match tampered.verify_signature_detailed() {
+        Err(SignatureError::Invalid(_)) => {},
+        result => panic!("Unexpected result: {:?}", result),
+    }
+    assert!(!tampered.verify_signature());
+
+    assert_eq!(peer_address(ty).verify_signature_detailed(), Err(SignatureError::Missing));
+}
+
+#[test]
+fn test_host_port() {
+    let ws = PeerAddressType::Ws(String::from("seed-20.nimiq.com"), 8080);
+    assert_eq!(ws.host_port(), Some(("seed-20.nimiq.com", 8080)));
+    let wss = PeerAddressType::Wss(String::from("seed-20.nimiq.com"), 8443);
+    assert_eq!(wss.host_port(), Some(("seed-20.nimiq.com", 8443)));
+    assert_eq!(peer_address(wss).host_port(), Some(("seed-20.nimiq.com", 8443)));
+
+    assert_eq!(PeerAddressType::Dumb.host_port(), None);
+    assert_eq!(PeerAddressType::Rtc.host_port(), None);
+    assert_eq!(PeerAddressType::RtcSignaled(PeerId::from_str(PEER_ID).unwrap()).host_port(), None);
+    let onion = PeerAddressType::Onion(format!("{}.onion", "a".repeat(56)), 8443);
+    assert_eq!(onion.host_port(), None);
+    assert_eq!(peer_address(onion).host_port(), None);
+}
+
+#[test]
+fn test_provides() {
+    let mut peer_address = peer_address(PeerAddressType::Dumb);
+    peer_address.services = ServiceFlags::FULL | ServiceFlags::VALIDATOR;
+    assert!(peer_address.provides(ServiceFlags::FULL));
+    assert!(peer_address.provides(ServiceFlags::VALIDATOR));
+    assert!(peer_address.provides(ServiceFlags::FULL | ServiceFlags::VALIDATOR));
+    assert!(!peer_address.provides(ServiceFlags::LIGHT));
+    assert!(!peer_address.provides(ServiceFlags::FULL | ServiceFlags::LIGHT));
+    assert!(peer_address.provides(ServiceFlags::NONE));
+}
+
+#[test]
+fn test_is_secure() {
+    assert!(peer_address(PeerAddressType::Wss(String::from("seed-20.nimiq.com"), 8443)).is_secure());
+    assert!(!peer_address(PeerAddressType::Ws(String::from("seed-20.nimiq.com"), 8443)).is_secure());
+    assert!(!peer_address(PeerAddressType::Rtc).is_secure());
+    assert!(!peer_address(PeerAddressType::Dumb).is_secure());
+}
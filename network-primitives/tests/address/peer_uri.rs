@@ -1,6 +1,7 @@
 use std::str::FromStr;
 
-use network_primitives::address::PeerUri;
+use network_primitives::address::{PeerAddress, PeerAddressType, PeerId, PeerUri};
+use network_primitives::address::peer_uri::PeerUriError;
 use network_primitives::protocol::Protocol;
 
 
@@ -23,6 +24,20 @@ fn test_parse_uri_rtc() {
     assert_eq!(uri.peer_id(), Some(String::from("2b3f0f59334ef71ee7869b451139587f")).as_ref());
 }
 
+#[test]
+fn test_parse_uri_rtc_signaled() {
+    let uri = PeerUri::from_str("rtc://7849ac3049680be1ef762efe0d36e017/2b3f0f59334ef71ee7869b451139587f").unwrap();
+    assert_eq!(uri.protocol(), Protocol::Rtc);
+    assert_eq!(uri.hostname(), Some(String::from("7849ac3049680be1ef762efe0d36e017")).as_ref());
+    assert_eq!(uri.port(), None);
+    assert_eq!(uri.peer_id(), Some(String::from("2b3f0f59334ef71ee7869b451139587f")).as_ref());
+    assert_eq!(uri.to_string(), "rtc://7849ac3049680be1ef762efe0d36e017/2b3f0f59334ef71ee7869b451139587f");
+
+    // The signaling peer must be given by its ID, and only WebRTC peers have one.
+    assert!(PeerUri::from_str("rtc://seed-20.nimiq.com/2b3f0f59334ef71ee7869b451139587f").is_err());
+    assert!(PeerUri::from_str("dumb://7849ac3049680be1ef762efe0d36e017/2b3f0f59334ef71ee7869b451139587f").is_err());
+}
+
 #[test]
 fn test_parse_uri_ws_port_peerid() {
     let uri = PeerUri::from_str("ws://seed-20.nimiq.com:8443/2b3f0f59334ef71ee7869b451139587f").unwrap();
@@ -68,3 +83,102 @@ fn test_parse_uri_ws() {
     assert_eq!(uri.peer_id(), Some(String::from("2b3f0f59334ef71ee7869b451139587f")).as_ref());
 }
 
+
+#[test]
+fn test_parse_uri_without_authority() {
+    let uri = PeerUri::from_str("dumb:///2b3f0f59334ef71ee7869b451139587f").unwrap();
+    assert_eq!(uri.protocol(), Protocol::Dumb);
+    assert_eq!(uri.hostname(), None);
+    assert_eq!(uri.peer_id(), Some(String::from("2b3f0f59334ef71ee7869b451139587f")).as_ref());
+
+    let uri = PeerUri::from_str("rtc:///2b3f0f59334ef71ee7869b451139587f").unwrap();
+    assert_eq!(uri.protocol(), Protocol::Rtc);
+    assert_eq!(uri.peer_id(), Some(String::from("2b3f0f59334ef71ee7869b451139587f")).as_ref());
+}
+
+#[test]
+fn test_parse_uri_from_peer_address() {
+    let uri = PeerAddress::from_uri("wss://seed-20.nimiq.com:8443/2b3f0f59334ef71ee7869b451139587f").unwrap();
+    assert_eq!(uri.protocol(), Protocol::Wss);
+    assert_eq!(uri.hostname(), Some(String::from("seed-20.nimiq.com")).as_ref());
+    assert_eq!(uri.port(), Some(8443));
+    assert_eq!(uri.peer_id(), Some(String::from("2b3f0f59334ef71ee7869b451139587f")).as_ref());
+
+    assert!(PeerAddress::from_uri("dumb://").is_err());
+    assert!(PeerAddress::from_uri("foo://2b3f0f59334ef71ee7869b451139587f").is_err());
+}
+
+#[test]
+fn test_parse_uri_ipv6_zone_id() {
+    let uri = PeerUri::from_str("ws://[fe80::1%25eth0]:8443/2b3f0f59334ef71ee7869b451139587f").unwrap();
+    assert_eq!(uri.hostname(), Some(String::from("[fe80::1%eth0]")).as_ref());
+    assert_eq!(uri.port(), Some(8443));
+    assert_eq!(uri.to_string(), "ws://[fe80::1%25eth0]:8443/2b3f0f59334ef71ee7869b451139587f");
+
+    // An unencoded `%` is accepted as well.
+    let uri = PeerUri::from_str("ws://[fe80::1%eth0]:8443/2b3f0f59334ef71ee7869b451139587f").unwrap();
+    assert_eq!(uri.hostname(), Some(String::from("[fe80::1%eth0]")).as_ref());
+    assert_eq!(uri.to_string(), "ws://[fe80::1%25eth0]:8443/2b3f0f59334ef71ee7869b451139587f");
+
+    let uri = PeerUri::from_str("ws://[fe80::1]:8443").unwrap();
+    assert_eq!(uri.hostname(), Some(String::from("[fe80::1]")).as_ref());
+}
+
+#[test]
+fn test_default_port() {
+    assert_eq!(Protocol::Ws.default_port(), Some(80));
+    assert_eq!(Protocol::Wss.default_port(), Some(443));
+    assert_eq!(Protocol::Dumb.default_port(), None);
+    assert_eq!(Protocol::Rtc.default_port(), None);
+
+    let uri = PeerUri::from_str("wss://seed-20.nimiq.com").unwrap();
+    assert_eq!(uri.port(), None);
+    assert_eq!(uri.port_or_default(), Some(443));
+    let uri = PeerUri::from_str("ws://seed-20.nimiq.com:8443").unwrap();
+    assert_eq!(uri.port_or_default(), Some(8443));
+
+    let public_key = "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a";
+    let seed = PeerUri::from_str(&format!("ws://seed-20.nimiq.com/{}", public_key)).unwrap().as_seed_peer_address().unwrap();
+    assert_eq!(seed.as_uri().port(), Some(80));
+}
+
+#[test]
+fn test_peer_uri_round_trip() {
+    let cases = vec![
+        ("dumb://2b3f0f59334ef71ee7869b451139587f", PeerAddressType::Dumb),
+        ("rtc://2b3f0f59334ef71ee7869b451139587f", PeerAddressType::Rtc),
+        ("rtc://7849ac3049680be1ef762efe0d36e017/2b3f0f59334ef71ee7869b451139587f", PeerAddressType::RtcSignaled(PeerId::from_str("7849ac3049680be1ef762efe0d36e017").unwrap())),
+        ("ws://seed-20.nimiq.com:8080/2b3f0f59334ef71ee7869b451139587f", PeerAddressType::Ws(String::from("seed-20.nimiq.com"), 8080)),
+        ("wss://seed-20.nimiq.com:8443/2b3f0f59334ef71ee7869b451139587f", PeerAddressType::Wss(String::from("seed-20.nimiq.com"), 8443)),
+    ];
+    for (s, ty) in cases {
+        let uri = PeerUri::from_str(s).unwrap();
+        assert_eq!(uri.to_string(), s);
+        assert_eq!(PeerUri::from_str(&uri.to_string()).unwrap(), uri);
+        assert_eq!(uri.to_peer_address_type().unwrap(), ty);
+    }
+
+    // Missing ports are replaced by the protocol's default port.
+    let uri = PeerUri::from_str("wss://seed-20.nimiq.com").unwrap();
+    assert_eq!(uri.to_peer_address_type().unwrap(), PeerAddressType::Wss(String::from("seed-20.nimiq.com"), 443));
+    let host = format!("{}.onion", "a".repeat(52) + "2345");
+    assert!(PeerUri::from_str(&format!("onion://{}", host)).unwrap().to_peer_address_type().is_err());
+}
+
+#[test]
+fn test_parse_protocol() {
+    for protocol in [Protocol::Dumb, Protocol::Ws, Protocol::Wss, Protocol::Rtc, Protocol::Onion].iter() {
+        assert_eq!(Protocol::from_str(protocol.scheme()).unwrap(), *protocol);
+        assert_eq!(protocol.to_string(), protocol.scheme());
+    }
+    assert_eq!(Protocol::from_str("WSS").unwrap(), Protocol::Wss);
+    assert_eq!(Protocol::from_str("Dumb").unwrap(), Protocol::Dumb);
+    assert_eq!(Protocol::from_str("rTc").unwrap(), Protocol::Rtc);
+
+    match Protocol::from_str("http") {
+        Err(PeerUriError::UnknownProtocol) => (),
+        other => panic!("Unexpected result: {:?}", other),
+    }
+    assert!(Protocol::from_str("").is_err());
+    assert!(Protocol::from_str("wss ").is_err());
+}
@@ -144,7 +144,7 @@ impl PeerAddressBookState {
             Protocol::Rtc => {
                 self.rtc_addresses.insert(Arc::clone(&info.peer_address));
             },
-            Protocol::Dumb => { } // Dumb addresses are ignored.
+            Protocol::Dumb | Protocol::Onion => { } // Dumb and onion addresses are ignored.
         };
 
         // Index peer address info by peer address.
@@ -519,7 +519,7 @@ impl PeerAddressBook {
                     if state.rtc_addresses.len() >= MAX_SIZE_RTC {
                         return false;
                     },
-                Protocol::Dumb => {}, // Dumb addresses are only part of global limit.
+                Protocol::Dumb | Protocol::Onion => {}, // Dumb and onion addresses are only part of global limit.
             }
 
             // If we know the IP address of the sender, check that we don't exceed the maximum number of addresses per IP.
@@ -313,6 +313,8 @@ impl<B: AbstractBlockchain + 'static> ConnectionPoolState<B> {
             Protocol::Ws => update_checked!(self.peer_count_ws, update),
             Protocol::Rtc => update_checked!(self.peer_count_rtc, update),
             Protocol::Dumb => update_checked!(self.peer_count_dumb, update),
+            // Onion addresses are only relayed, we never connect to them.
+            Protocol::Onion => {},
         }
 
         if peer_address.services.is_full_node() {
@@ -172,45 +172,37 @@ impl NetworkConfig {
 
     pub fn peer_address(&self) -> PeerAddress {
         // TODO Check PeerAddress globally reachable.
-        let mut addr = PeerAddress {
-            ty: match self.protocol_config {
-                ProtocolConfig::Rtc => PeerAddressType::Rtc,
-                ProtocolConfig::Dumb => PeerAddressType::Dumb,
-                ProtocolConfig::Ws {
-                    ref host,
-                    port,
-                    ref reverse_proxy_config,
-                    ..
-                } => {
-                    if let Some(reverse_proxy_config) = reverse_proxy_config.as_ref() {
-                        if reverse_proxy_config.with_tls_termination {
-                            PeerAddressType::Wss(host.clone(), reverse_proxy_config.port)
-                        } else {
-                            PeerAddressType::Ws(host.clone(), reverse_proxy_config.port)
-                        }
+        let key_pair = self.key_pair.as_ref().expect("NetworkConfig is uninitialized");
+        let ty = match self.protocol_config {
+            ProtocolConfig::Rtc => PeerAddressType::Rtc,
+            ProtocolConfig::Dumb => PeerAddressType::Dumb,
+            ProtocolConfig::Ws {
+                ref host,
+                port,
+                ref reverse_proxy_config,
+                ..
+            } => {
+                if let Some(reverse_proxy_config) = reverse_proxy_config.as_ref() {
+                    if reverse_proxy_config.with_tls_termination {
+                        PeerAddressType::Wss(host.clone(), reverse_proxy_config.port)
                     } else {
-                        PeerAddressType::Ws(host.clone(), port)
+                        PeerAddressType::Ws(host.clone(), reverse_proxy_config.port)
                     }
-                },
-                ProtocolConfig::Wss {
-                    ref host,
-                    port,
-                    ..
-                } => PeerAddressType::Wss(host.clone(), port),
+                } else {
+                    PeerAddressType::Ws(host.clone(), port)
+                }
             },
-            services: self.services.provided,
-            timestamp: systemtime_to_timestamp(SystemTime::now()),
-            net_address: NetAddress::Unspecified,
-            public_key: self.key_pair.as_ref().expect("NetworkConfig is uninitialized").public,
-            distance: 0,
-            signature: None,
-            peer_id: self.peer_id.as_ref().expect("NetworkConfig is uninitialized").clone(),
+            ProtocolConfig::Wss {
+                ref host,
+                port,
+                ..
+            } => PeerAddressType::Wss(host.clone(), port),
         };
+        let addr = PeerAddress::new_signed(ty, self.services.provided, systemtime_to_timestamp(SystemTime::now()), NetAddress::Unspecified, key_pair, 0);
         if addr.protocol() == Protocol::Wss || addr.protocol() == Protocol::Ws {
             // TODO Disabled for debugging
             //assert!(addr.is_globally_reachable(false), "PeerAddress not globally reachable.");
         }
-        addr.signature = Some(self.key_pair.as_ref().expect("NetworkConfig is uninitialized").sign(&addr.get_signature_data()[..]));
         addr
     }
 
@@ -169,6 +169,7 @@ pub enum PeerProtocol {
     Wss,
     Rtc,
     Ws,
+    Onion,
     Unknown,
 }
 
@@ -179,6 +180,7 @@ impl Display for PeerProtocol {
             PeerProtocol::Wss => "websocket-secure",
             PeerProtocol::Ws => "websocket",
             PeerProtocol::Rtc => "webrtc",
+            PeerProtocol::Onion => "onion",
             PeerProtocol::Unknown => "unknown",
         })
     }
@@ -191,6 +193,7 @@ impl From<Protocol> for PeerProtocol {
             Protocol::Ws => PeerProtocol::Ws,
             Protocol::Wss => PeerProtocol::Wss,
             Protocol::Rtc => PeerProtocol::Rtc,
+            Protocol::Onion => PeerProtocol::Onion,
         }
     }
 }
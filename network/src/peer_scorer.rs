@@ -72,7 +72,8 @@ impl<B: AbstractBlockchain + 'static> PeerScorer<B> {
         if candidates.is_empty() {
             return None;
         }
-        candidates.sort_by(|a, b| { a.1.cmp(&b.1) });
+        // Among addresses with the same score, prefer secure ones.
+        candidates.sort_by(|a, b| { a.1.cmp(&b.1).then_with(|| b.0.is_secure().cmp(&a.0.is_secure())) });
         let rand_ind = OsRng.gen_range(0, usize::min(Self::PICK_SELECTION_SIZE, candidates.len()));
         match candidates.get(rand_ind) {
             Some((peer_address, _)) => Some(Arc::clone(peer_address)),
@@ -239,7 +240,8 @@ impl<B: AbstractBlockchain + 'static> PeerScorer<B> {
                 }
             },
             Protocol::Rtc => 0.3,
-            Protocol::Dumb => 0.0,
+            // We can't dial onion addresses ourselves.
+            Protocol::Dumb | Protocol::Onion => 0.0,
         };
 
         // Connection speed, based on ping-pong latency median
@@ -2,7 +2,7 @@ use std::cmp::Ordering;
 use std::io;
 
 use beserial::{Deserialize, Serialize};
-use hash::{Blake2bHash, Hash, SerializeContent};
+use hash::{Blake2bHash, Blake2bHasher, Hash, Hasher, SerializeContent};
 use nimiq_bls::bls12_381::{CompressedSignature, PublicKey};
 use primitives::policy;
 
@@ -19,6 +19,36 @@ pub struct ForkProof {
 impl ForkProof {
     pub const SIZE: usize = 2 * MicroHeader::SIZE + 2 * 48;
 
+    /// Creates a fork proof from two headers, checking that they are for the same block and
+    /// view number but different. The justifications are not verified, use `verify` for that.
+    pub fn new(header1: MicroHeader, justification1: CompressedSignature, header2: MicroHeader, justification2: CompressedSignature) -> Result<Self, ForkProofError> {
+        let fork_proof = ForkProof {
+            header1,
+            header2,
+            justification1,
+            justification2,
+        };
+        fork_proof.check_consistency()?;
+        Ok(fork_proof)
+    }
+
+    /// Whether the headers are for the same block and view number but different, i.e. whether
+    /// this proves a fork if the justifications are valid.
+    pub fn is_self_consistent(&self) -> bool {
+        self.check_consistency().is_ok()
+    }
+
+    fn check_consistency(&self) -> Result<(), ForkProofError> {
+        if self.header1.block_number != self.header2.block_number
+            || self.header1.view_number != self.header2.view_number {
+            return Err(ForkProofError::SlotMismatch);
+        }
+        if self.header1.hash::<Blake2bHash>() == self.header2.hash::<Blake2bHash>() {
+            return Err(ForkProofError::SameHeader);
+        }
+        Ok(())
+    }
+
     pub fn verify(&self, public_key: &PublicKey) -> Result<(), ForkProofError> {
         // XXX Duplicate check
         if self.header1.block_number != self.header2.block_number
@@ -39,6 +69,36 @@ impl ForkProof {
         Ok(())
     }
 
+    /// Orders the two headers (and their justifications) by block hash, so that proofs for the
+    /// same offense with swapped headers have the same serialization and hash.
+    pub fn canonical(self) -> ForkProof {
+        if self.header1.hash::<Blake2bHash>() > self.header2.hash::<Blake2bHash>() {
+            ForkProof {
+                header1: self.header2,
+                header2: self.header1,
+                justification1: self.justification2,
+                justification2: self.justification1,
+            }
+        } else {
+            self
+        }
+    }
+
+    /// The hash of `self.clone().canonical()`, computed without copying the proof.
+    pub fn canonical_hash(&self) -> Blake2bHash {
+        if self.header1.hash::<Blake2bHash>() > self.header2.hash::<Blake2bHash>() {
+            // Hash the serialization `canonical` would produce, i.e. with the headers swapped.
+            let mut hasher = Blake2bHasher::default();
+            self.header2.serialize(&mut hasher).unwrap();
+            self.header1.serialize(&mut hasher).unwrap();
+            self.justification2.serialize(&mut hasher).unwrap();
+            self.justification1.serialize(&mut hasher).unwrap();
+            hasher.finish()
+        } else {
+            self.hash()
+        }
+    }
+
     pub fn is_valid_at(&self, block_number: u32) -> bool {
         let given_epoch = policy::epoch_at(block_number);
         let proof_epoch = policy::epoch_at(self.header1.block_number);
@@ -48,10 +108,19 @@ impl ForkProof {
             && (proof_epoch == given_epoch || proof_epoch + 1 == given_epoch)
     }
 
+    /// Priority of this proof when it slashes a validator with `slot_weight` slots: proofs with a
+    /// higher weight, then a higher view number, then a higher block number have a higher
+    /// priority. Pools that are full evict the proof with the lowest priority first.
+    pub fn eviction_priority(&self, slot_weight: u16) -> (u16, u32, u32) {
+        (slot_weight, self.view_number(), self.block_number())
+    }
+
+    /// The block number of the fork. For a self-consistent proof, both headers have it.
     pub fn block_number(&self) -> u32 {
         self.header1.block_number
     }
 
+    /// The view number of the fork. For a self-consistent proof, both headers have it.
     pub fn view_number(&self) -> u32 {
         self.header1.view_number
     }
@@ -112,5 +181,6 @@ impl std::hash::Hash for ForkProof {
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum ForkProofError {
     SlotMismatch,
+    SameHeader,
     InvalidJustification,
 }
@@ -27,8 +27,8 @@ pub mod signed;
 pub use block::{Block, BlockType, BlockHeader};
 pub use macro_block::{MacroBlock, MacroHeader, MacroExtrinsics};
 pub use micro_block::{MicroBlock, MicroHeader, MicroJustification, MicroExtrinsics};
-pub use view_change::{ViewChange, SignedViewChange, ViewChangeProof, ViewChangeProofBuilder, ViewChanges};
-pub use fork_proof::ForkProof;
+pub use view_change::{ViewChange, SignedViewChange, ViewChangeProof, ViewChangeProofBuilder, ViewChangeProofError, ViewChanges, verify_signed_view_changes};
+pub use fork_proof::{ForkProof, ForkProofError};
 pub use pbft::{PbftPrepareMessage, PbftCommitMessage, PbftProofBuilder, PbftProof, SignedPbftPrepareMessage, SignedPbftCommitMessage, SignedPbftProposal, PbftProposal};
 
 use crate::transaction::TransactionError;
@@ -107,6 +107,22 @@ impl PbftProofBuilder {
         Ok(())
     }
 
+    /// Whether the prepare signatures added so far reach `threshold` slots
+    pub fn has_prepare_threshold(&self, threshold: u16) -> bool {
+        self.prepare.has_threshold(threshold)
+    }
+
+    /// Whether the commit signatures added so far reach `threshold` slots
+    pub fn has_commit_threshold(&self, threshold: u16) -> bool {
+        self.commit.has_threshold(threshold)
+    }
+
+    /// Whether both phases reach `threshold` slots. Note that `verify` additionally requires
+    /// `threshold` slots to have signed both the prepare and the commit.
+    pub fn has_threshold(&self, threshold: u16) -> bool {
+        self.has_prepare_threshold(threshold) && self.has_commit_threshold(threshold)
+    }
+
     pub fn add_prepare_signature(&mut self, public_key: &PublicKey, num_slots: u16, prepare: &SignedPbftPrepareMessage) -> bool {
         self.prepare.add_signature(public_key, num_slots, prepare)
     }
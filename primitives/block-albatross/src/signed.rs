@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::fmt::Debug;
 use std::marker::PhantomData;
 
@@ -7,6 +8,7 @@ use bls::SigHash;
 use hash::{Blake2bHasher, SerializeContent, Hasher};
 use collections::bitset::BitSet;
 use primitives::slot::{ValidatorSlots, SlotCollection, SlotIndex, SlotBand};
+use utils::key_rng::{Rng, SecureRng};
 
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -44,6 +46,55 @@ impl<M: Message> SignedMessage<M> {
             signature,
         }
     }
+    /// The signed message, without the prefix.
+    pub fn message(&self) -> &M {
+        &self.message
+    }
+
+    /// Index of the validator that signed the message.
+    pub fn signer_index(&self) -> u16 {
+        self.signer_idx
+    }
+}
+
+/// Verifies a batch of signed messages against the public keys of their signers. Signatures over
+/// the same message are aggregated and checked with a single pairing. Each signature and public
+/// key is multiplied by a random 64-bit coefficient before aggregating, so that invalid signatures
+/// can't add up to a valid aggregate. If the batch doesn't verify, the messages are checked one by
+/// one and the index of the first invalid one (or the first one with an unknown signer) is
+/// returned.
+/// Expects valid validator public keys.
+pub fn verify_signed_messages<M: Message>(messages: &[SignedMessage<M>], validators: &ValidatorSlots) -> Result<(), usize> {
+    if verify_signed_messages_aggregated(messages, validators) {
+        return Ok(());
+    }
+
+    for (i, signed) in messages.iter().enumerate() {
+        let validator = validators.get_by_band_number(signed.signer_idx).ok_or(i)?;
+        if !signed.verify(&validator.public_key().uncompress_unchecked()) {
+            return Err(i);
+        }
+    }
+    Ok(())
+}
+
+fn verify_signed_messages_aggregated<M: Message>(messages: &[SignedMessage<M>], validators: &ValidatorSlots) -> bool {
+    let mut rng = SecureRng::default();
+    let mut aggregates: HashMap<SigHash, (AggregatePublicKey, AggregateSignature)> = HashMap::new();
+    for signed in messages {
+        let validator = match validators.get_by_band_number(signed.signer_idx) {
+            Some(validator) => validator,
+            None => return false,
+        };
+        let (public_key, signature) = aggregates.entry(signed.message.hash_with_prefix())
+            .or_insert_with(|| (AggregatePublicKey::new(), AggregateSignature::new()));
+        // A zero coefficient would drop the signature from the batch.
+        let coefficient = rng.gen::<u64>().max(1);
+        public_key.aggregate_weighted(&validator.public_key().uncompress_unchecked(), coefficient);
+        signature.aggregate_weighted(&signed.signature, coefficient);
+    }
+    aggregates.into_iter()
+        .all(|(hash, (public_key, signature))| public_key.verify_hash(hash, &signature))
 }
 
 
@@ -51,6 +102,14 @@ impl<M: Message> SignedMessage<M> {
 // a validator) must be distinguishable!
 // Therefore all signed messages should be prefixed with a standardized type. We should keep those
 // prefixed at one place to not accidentally create collisions.
+//
+// Assigned prefixes (new message types must pick an unused one and add it to `PREFIXES`):
+//  - 0x01: `ViewChange`
+//  - 0x02: `PbftProposal` and `MacroHeader` (the proposal's content starts with the header)
+//  - 0x03: `PbftPrepareMessage`
+//  - 0x04: `PbftCommitMessage`
+//  - 0x05: proof of knowledge of the secret key
+//  - 0x06: `ValidatorInfo` (in `nimiq-network-primitives`)
 
 /// prefix to sign view change messages
 pub const PREFIX_VIEW_CHANGE: u8 = 0x01;
@@ -65,8 +124,20 @@ pub const PREFIX_POKOSK: u8 = 0x05;
 /// prefix to sign a validator info
 pub const PREFIX_VALIDATOR_INFO: u8 = 0x06;
 
+/// All assigned prefixes
+pub const PREFIXES: [u8; 6] = [
+    PREFIX_VIEW_CHANGE,
+    PREFIX_PBFT_PROPOSAL,
+    PREFIX_PBFT_PREPARE,
+    PREFIX_PBFT_COMMIT,
+    PREFIX_POKOSK,
+    PREFIX_VALIDATOR_INFO,
+];
+
 
 pub trait Message: Clone + Debug + Serialize + Deserialize + SerializeContent + Send + Sync + Sized + PartialEq + 'static {
+    /// Prefix that is hashed before the message when signing, so that signatures for one message
+    /// type can't be used for another. Use one of the `PREFIX_*` constants.
     const PREFIX: u8;
 
     fn hash_with_prefix(&self) -> SigHash {
@@ -81,7 +152,9 @@ pub trait Message: Clone + Debug + Serialize + Deserialize + SerializeContent +
     }
 }
 
+#[derive(Clone, Debug, PartialEq, Eq, Fail)]
 pub enum AggregateError {
+    #[fail(display = "Overlapping signers")]
     Overlapping
 }
 
@@ -119,7 +192,9 @@ impl<M: Message> AggregateProofBuilder<M> {
         self.signers.contains(signed.signer_idx as usize)
     }
 
-    /// Adds a signed message to an aggregate proof
+    /// Adds a signed message to an aggregate proof. Returns `false` and leaves the proof unchanged
+    /// if a signature of the same signer was already added, so a signer's slots are never counted
+    /// twice.
     /// NOTE: This method assumes the signature of the message was already checked
     pub fn add_signature(&mut self, public_key: &PublicKey, num_slots: u16, signed: &SignedMessage<M>) -> bool {
         debug_assert!(signed.verify(public_key));
@@ -134,6 +209,31 @@ impl<M: Message> AggregateProofBuilder<M> {
         true
     }
 
+    /// Like `add_signature`, but takes the signer's public key and number of slots from
+    /// `validators`. Returns `false` and leaves the proof unchanged if `signer_idx` isn't the
+    /// signer of `signed` or not a validator in `validators`, or if the signer was already added.
+    /// NOTE: This method assumes the signature of the message was already checked
+    pub fn add_signature_for(&mut self, validators: &ValidatorSlots, signer_idx: u16, signed: &SignedMessage<M>) -> bool {
+        if signed.signer_idx != signer_idx {
+            return false;
+        }
+        match validators.get_by_band_number(signer_idx) {
+            Some(validator) => self.add_signature(&validator.public_key().uncompress_unchecked(), validator.num_slots(), signed),
+            None => false,
+        }
+    }
+
+    /// The cumulative number of slots of the signers added so far. This is the weight `verify`
+    /// checks against the threshold.
+    pub fn signed_weight(&self) -> u16 {
+        self.num_slots
+    }
+
+    /// Whether the signers added so far hold at least `threshold` slots.
+    pub fn has_threshold(&self, threshold: u16) -> bool {
+        self.num_slots >= threshold
+    }
+
     #[allow(unused_variables)]
     pub fn merge(&mut self, proof: &AggregateProof<M>) -> Result<Self, AggregateError> {
         unimplemented!()
@@ -174,6 +274,10 @@ impl<M: Message> Default for AggregateProofBuilder<M> {
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+/// Serialized as the signer bitset followed by the aggregate signature. Proofs don't need to reach
+/// the threshold to be serialized, so partial aggregates can be sent to other nodes, which merge
+/// in more signatures.
+///
 /// TODO: Maybe refactor this, since we only construct those from Handel MultiSignatures now.
 pub struct AggregateProof<M: Message> {
     /// Indices of validators that signed this proof
@@ -195,6 +299,33 @@ impl<M: Message> AggregateProof<M> {
         }
     }
 
+    /// Indices of the validators that signed this proof
+    pub fn signers(&self) -> &BitSet {
+        &self.signers
+    }
+
+    /// Merges `other` into this proof, so that it is signed by the signers of both proofs. If
+    /// both proofs are valid for the same message, so is the merged proof.
+    ///
+    /// Signatures can't be removed from an aggregate signature, so if the signer sets overlap,
+    /// the proofs can only be merged if one contains the other. Otherwise `Overlapping` is
+    /// returned and this proof is left unchanged.
+    pub fn merge(&mut self, other: &AggregateProof<M>) -> Result<(), AggregateError> {
+        if self.signers.is_superset(&other.signers) {
+            return Ok(());
+        }
+        if self.signers.is_subset(&other.signers) {
+            *self = other.clone();
+            return Ok(());
+        }
+        if self.signers.intersection_size(&other.signers) > 0 {
+            return Err(AggregateError::Overlapping);
+        }
+        self.signers = &self.signers | &other.signers;
+        self.signature.merge_into(&other.signature);
+        Ok(())
+    }
+
     pub fn votes(&self, validators: &ValidatorSlots) -> Result<u16, AggregateProofError> {
         votes_for_signers(validators, &self.signers)
     }
@@ -1,13 +1,18 @@
+use std::cmp::Ordering;
 use std::fmt;
 
 use beserial::{Deserialize, Serialize};
 use hash::SerializeContent;
 use hash_derive::SerializeContent;
+use primitives::slot::ValidatorSlots;
 use vrf::VrfSeed;
 
 use super::signed;
 
-#[derive(Clone, Debug, Ord, PartialOrd, Eq, PartialEq, Serialize, Deserialize, SerializeContent, Hash)]
+/// View changes are ordered by block number, then by new view number, i.e. for the same block
+/// number, the view change with the higher new view number is greater. The seed only breaks ties,
+/// so that the ordering is consistent with equality.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, SerializeContent, Hash)]
 pub struct ViewChange {
     /// The hash of the previous block.
     /// This is needed to distinguish view changes on different branches.
@@ -16,7 +21,8 @@ pub struct ViewChange {
     pub prev_seed: VrfSeed,
 
     /// The number of the block for which the view change is constructed (i.e. the block number
-    /// the validator is at + 1, since it's for the next block)
+    /// the validator is at + 1, since it's for the next block). Like all fields, it is part of
+    /// the signed message, so signatures can't be replayed for other block numbers.
     pub block_number: u32,
 
     /// The view number after the view_change (i.e. the current view number + 1, except if the view
@@ -24,6 +30,28 @@ pub struct ViewChange {
     pub new_view_number: u32,
 }
 
+impl ViewChange {
+    /// Whether this view change is newer than `other`, i.e. it is for a later block or for the
+    /// same block but a higher new view number. The seed is ignored.
+    pub fn supersedes(&self, other: &ViewChange) -> bool {
+        (self.block_number, self.new_view_number) > (other.block_number, other.new_view_number)
+    }
+}
+
+impl Ord for ViewChange {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.block_number.cmp(&other.block_number)
+            .then_with(|| self.new_view_number.cmp(&other.new_view_number))
+            .then_with(|| self.prev_seed.cmp(&other.prev_seed))
+    }
+}
+
+impl PartialOrd for ViewChange {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 impl signed::Message for ViewChange {
     const PREFIX: u8 = signed::PREFIX_VIEW_CHANGE;
 }
@@ -31,6 +59,16 @@ impl signed::Message for ViewChange {
 pub type SignedViewChange = signed::SignedMessage<ViewChange>;
 pub type ViewChangeProof = signed::AggregateProof<ViewChange>;
 pub type ViewChangeProofBuilder = signed::AggregateProofBuilder<ViewChange>;
+/// Error returned by `ViewChangeProof::verify`. Distinguishes an unknown signer index, an
+/// invalid aggregate signature and insufficient signer weight (with the number of slots signed
+/// and required).
+pub type ViewChangeProofError = signed::AggregateProofError;
+
+/// Verifies a batch of signed view changes, returning the index of the first invalid one on
+/// failure. See `signed::verify_signed_messages`.
+pub fn verify_signed_view_changes(messages: &[SignedViewChange], validators: &ValidatorSlots) -> Result<(), usize> {
+    signed::verify_signed_messages(messages, validators)
+}
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ViewChanges {
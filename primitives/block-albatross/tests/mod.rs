@@ -1,13 +1,16 @@
+use std::collections::HashSet;
 use std::convert::TryInto;
 use std::str::FromStr;
 
 use beserial::Deserialize;
-use nimiq_block_albatross::{MacroBlock, MacroExtrinsics, MacroHeader};
+use nimiq_block_albatross::{MacroBlock, MacroExtrinsics, MacroHeader, PbftCommitMessage, PbftPrepareMessage, PbftProposal, ViewChange};
+use nimiq_block_albatross::signed::{self, Message};
 use nimiq_bls::bls12_381::{Signature, CompressedPublicKey};
 use nimiq_collections::bitset::BitSet;
 use nimiq_hash::{Blake2bHasher, Hasher};
 use nimiq_keys::Address;
 use nimiq_primitives::slot::{Slots, StakeSlots, StakeSlotBand, ValidatorSlots, ValidatorSlotBand};
+use nimiq_vrf::VrfSeed;
 
 #[test]
 fn it_can_convert_macro_block_into_slots() {
@@ -66,3 +69,33 @@ fn it_can_convert_macro_block_into_slots() {
 
     assert_eq!(slots, slots_from_macro);
 }
+
+#[test]
+fn it_has_distinct_signing_prefixes() {
+    let prefixes: HashSet<u8> = signed::PREFIXES.iter().cloned().collect();
+    assert_eq!(prefixes.len(), signed::PREFIXES.len());
+
+    assert_eq!(ViewChange::PREFIX, signed::PREFIX_VIEW_CHANGE);
+    assert_eq!(PbftProposal::PREFIX, signed::PREFIX_PBFT_PROPOSAL);
+    assert_eq!(MacroHeader::PREFIX, signed::PREFIX_PBFT_PROPOSAL);
+    assert_eq!(PbftPrepareMessage::PREFIX, signed::PREFIX_PBFT_PREPARE);
+    assert_eq!(PbftCommitMessage::PREFIX, signed::PREFIX_PBFT_COMMIT);
+}
+
+#[test]
+fn it_orders_view_changes_by_block_and_view_number() {
+    let view_change = |block_number, new_view_number| ViewChange { prev_seed: VrfSeed::default(), block_number, new_view_number };
+
+    assert!(view_change(10, 2) > view_change(10, 1));
+    assert!(view_change(11, 0) > view_change(10, 5));
+    assert_eq!(view_change(10, 1).cmp(&view_change(10, 1)), std::cmp::Ordering::Equal);
+
+    assert!(view_change(10, 2).supersedes(&view_change(10, 1)));
+    assert!(view_change(11, 0).supersedes(&view_change(10, 5)));
+    assert!(!view_change(10, 1).supersedes(&view_change(10, 1)));
+    assert!(!view_change(10, 1).supersedes(&view_change(10, 2)));
+
+    let mut view_changes = vec![view_change(10, 3), view_change(9, 7), view_change(10, 1)];
+    view_changes.sort();
+    assert_eq!(view_changes, vec![view_change(9, 7), view_change(10, 1), view_change(10, 3)]);
+}
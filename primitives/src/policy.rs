@@ -116,6 +116,9 @@ pub const UNSTAKING_DELAY: u32 = 100; // TODO: Set.
 pub const SLOTS: u16 = 512;
 
 /// ceil(2/3) of active validators
+///
+/// This assumes that the validators hold all `SLOTS` slots. For a validator set that may hold
+/// fewer, use `ValidatorSlots::two_third_threshold` instead.
 // (2 * n + 3) / 3 = ceil(2f + 1) where n = 3f + 1
 pub const TWO_THIRD_SLOTS: u16 = (2 * SLOTS + 3) / 3;
 
@@ -56,7 +56,7 @@ use itertools::Itertools;
 
 use beserial::{Deserialize, Serialize, ReadBytesExt, WriteBytesExt, SerializingError, SerializeWithLength, DeserializeWithLength, uvar};
 use bls::bls12_381::lazy::LazyPublicKey;
-use bls::bls12_381::CompressedPublicKey;
+use bls::bls12_381::{CompressedPublicKey, PublicKey};
 use keys::Address;
 
 use crate::policy::SLOTS;
@@ -325,10 +325,32 @@ impl ValidatorSlots {
             .find_position(|validator| validator.public_key.compressed() == public_key)
             .map(|(idx, validator)| (idx as u16, validator.num_slots))
     }
-    
+
+    /// Returns the band number of the validator with the given public key.
+    ///
+    /// Keys are compared in their compressed representation, so the lookup works regardless of
+    /// whether the band's `LazyPublicKey` has been uncompressed yet. To look up a
+    /// `CompressedPublicKey` directly, use `find_idx_and_num_slots_by_public_key`.
+    pub fn index_of(&self, public_key: &PublicKey) -> Option<u16> {
+        self.find_idx_and_num_slots_by_public_key(&public_key.compress())
+            .map(|(idx, _)| idx)
+    }
+
     pub fn iter(&self) -> Iter<ValidatorSlotBand> {
         self.bands.iter()
     }
+
+    /// Total number of slots held by these validators.
+    pub fn num_slots(&self) -> u16 {
+        self.bands.iter().map(|band| band.num_slots).sum()
+    }
+
+    /// Number of slots needed for a two-third majority of these validators, computed like
+    /// `policy::TWO_THIRD_SLOTS` but from the slots actually held by them. The two agree if the
+    /// validators hold all `policy::SLOTS` slots.
+    pub fn two_third_threshold(&self) -> u16 {
+        ((2 * u32::from(self.num_slots()) + 3) / 3) as u16
+    }
 }
 
 impl SlotCollection for ValidatorSlots {
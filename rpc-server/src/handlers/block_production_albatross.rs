@@ -52,11 +52,16 @@ impl BlockProductionAlbatrossHandler {
             "proofOfKnowledge" => proof_of_knowledge.to_string(),
         })
     }
+
+    fn pending_slash_weight(&self, _params: &[JsonValue]) -> Result<JsonValue, JsonValue> {
+        Ok(self.validator.pending_slash_weight().into())
+    }
 }
 
 impl Module for BlockProductionAlbatrossHandler {
     rpc_module_methods! {
         "validatorKey" => validator_key,
         "proofOfKnowledge" => proof_of_knowledge,
+        "pendingSlashWeight" => pending_slash_weight,
     }
 }
@@ -434,8 +434,8 @@ impl BlockchainAlbatrossHandler {
 
     fn fork_proof_to_obj(fork_proof: &ForkProof) -> JsonValue {
         object! {
-            "blockNumber" => fork_proof.header1.block_number,
-            "viewNumber" => fork_proof.header1.view_number,
+            "blockNumber" => fork_proof.block_number(),
+            "viewNumber" => fork_proof.view_number(),
             "parentHash" => fork_proof.header1.parent_hash.to_hex(),
             "hashes" => vec![
                 fork_proof.header1.hash::<Blake2bHash>().to_hex(),
@@ -27,6 +27,8 @@ pub mod validator_network;
 pub mod validator_agent;
 pub mod error;
 pub mod slash;
+#[cfg(feature = "metrics")]
+pub mod slash_metrics;
 pub mod signature_aggregation;
 pub mod pool;
 
@@ -1,56 +1,1012 @@
-use block_albatross::{ForkProof, Block, MicroBlock};
-use std::collections::HashSet;
-use beserial::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::sync::Arc;
 
-#[derive(Default)]
-pub struct ForkProofPool {
-    fork_proofs: HashSet<ForkProof>,
+use failure::Fail;
+
+use beserial::{DeserializeWithLength, Serialize, SerializeWithLength, SerializingError};
+use block_albatross::{Block, ForkProof, ForkProofError, MicroBlock};
+use blockchain_albatross::Blockchain;
+use blockchain_albatross::blockchain::BlockchainState;
+use bls::bls12_381::{CompressedPublicKey, PublicKey};
+use collections::bitset::BitSet;
+use hash::{Blake2bHash, Hash};
+use primitives::policy;
+use primitives::slot::{Slot, SlotBand};
+
+#[cfg(feature = "metrics")]
+use crate::slash_metrics::ForkProofPoolMetrics;
+
+#[derive(Fail, Debug, Clone, PartialEq, Eq)]
+pub enum ForkProofPoolError {
+    #[fail(display = "Fork proof targets an epoch that can't be slashed anymore")]
+    InvalidEpochTarget,
+    #[fail(display = "Fork proof refers to an unknown block")]
+    UnexpectedBlock,
+    #[fail(display = "Slot has already been slashed")]
+    SlotAlreadySlashed,
+    #[fail(display = "Invalid fork proof: {:?}", _0)]
+    InvalidForkProof(ForkProofError),
+    #[fail(display = "Fork proof pool is full")]
+    PoolFull,
+    #[fail(display = "Offending validator holds too few slots")]
+    InsufficientWeight,
+}
+
+impl From<ForkProofError> for ForkProofPoolError {
+    fn from(e: ForkProofError) -> Self {
+        ForkProofPoolError::InvalidForkProof(e)
+    }
+}
+
+/// Result of a successful `ForkProofPool::insert`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InsertOutcome {
+    /// The fork proof has been added to the pool.
+    Added,
+    /// This exact fork proof is already in the pool.
+    AlreadyKnown,
+    /// A different fork proof for the same slot is already in the pool.
+    SlotOccupied,
+}
+
+/// Result of `ForkProofPool::merge`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MergeSummary {
+    /// Number of fork proofs that have been added.
+    pub added: usize,
+    /// Number of fork proofs that were already known or whose slot was occupied.
+    pub ignored: usize,
+    /// Number of fork proofs that have been rejected, e.g. because they are invalid or the pool
+    /// is full.
+    pub rejected: usize,
+}
+
+/// Change to the contents of a `ForkProofPool`, passed to its listener. Both variants carry the
+/// hash of the fork proof and the slot number it slashes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ForkProofPoolEvent {
+    Added(Blake2bHash, u16),
+    Removed(Blake2bHash, u16),
+}
+
+/// Listener for `ForkProofPoolEvent`s, see `ForkProofPool::set_listener`.
+pub type ForkProofPoolListener = Box<dyn Fn(ForkProofPoolEvent) + Send + Sync>;
+
+/// A fork proof in the pool, together with the slot it slashes.
+#[derive(Clone)]
+struct PooledForkProof {
+    fork_proof: ForkProof,
+    slot_number: u16,
+    /// Public key of the offending validator.
+    validator: CompressedPublicKey,
+    /// Number of slots the offending validator holds.
+    weight: u16,
+    /// Time the proof was inserted at, as given to `ForkProofPool::insert_with_timestamp`.
+    inserted_at: u64,
+}
+
+/// Summary of a pooled fork proof, as returned by `ForkProofPool::report`.
+///
+/// With the `serde` feature, reports can be serialized (e.g. to JSON for an admin endpoint). The
+/// hash is serialized as a hex string.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ForkProofReport {
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_hex"))]
+    pub hash: Blake2bHash,
+    pub slot_number: u16,
+    pub block_number: u32,
+    pub view_number: u32,
+    pub epoch: u32,
+}
+
+#[cfg(feature = "serde")]
+fn serialize_hex<S: serde::Serializer>(hash: &Blake2bHash, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&hash.to_hex())
+}
+
+/// The chain information `ForkProofPool` needs to validate fork proofs.
+pub trait SlotSnapshot {
+    /// Block number of the chain head.
+    fn state_height(&self) -> u32;
+
+    /// Slashed set of `epoch`, if it can still be slashed (i.e. it is the current or the previous
+    /// epoch).
+    fn slashed_set_for_epoch(&self, epoch: u32) -> Option<BitSet>;
+
+    /// Returns the slot and slot number that produces the block at `block_number` and
+    /// `view_number`, like `Blockchain::get_slot_at`.
+    fn get_slot_at(&self, block_number: u32, view_number: u32) -> Option<(Slot, u16)>;
+}
+
+/// Source of the chain information `ForkProofPool` needs. This is implemented for `Blockchain`,
+/// but allows testing the pool without a full blockchain.
+///
+/// Each lookup through `SlotSnapshot` sees the chain state at the time of the lookup, so
+/// consecutive lookups may see different states if blocks are pushed in between.
+pub trait SlotResolver: SlotSnapshot {
+    /// Calls `f` with a snapshot of the chain that doesn't change while `f` runs, e.g. to
+    /// validate several fork proofs against the same state.
+    ///
+    /// The default implementation passes the resolver itself, which is only correct for
+    /// resolvers whose state can't change concurrently.
+    fn with_snapshot<T, F: FnOnce(&dyn SlotSnapshot) -> T>(&self, f: F) -> T where Self: Sized {
+        f(self)
+    }
+}
+
+/// Slashed set of `epoch` in `state`, see `SlotSnapshot::slashed_set_for_epoch`.
+fn slashed_set_of(state: &BlockchainState, epoch: u32) -> Option<BitSet> {
+    let current_epoch = policy::epoch_at(state.block_number());
+    if epoch == current_epoch {
+        Some(state.current_slashed_set())
+    } else if epoch + 1 == current_epoch {
+        Some(state.last_slashed_set())
+    } else {
+        None
+    }
+}
+
+impl SlotSnapshot for Blockchain {
+    fn state_height(&self) -> u32 {
+        self.block_number()
+    }
+
+    fn slashed_set_for_epoch(&self, epoch: u32) -> Option<BitSet> {
+        slashed_set_of(&self.state(), epoch)
+    }
+
+    fn get_slot_at(&self, block_number: u32, view_number: u32) -> Option<(Slot, u16)> {
+        Blockchain::get_slot_at(self, block_number, view_number, None)
+    }
+}
+
+impl SlotResolver for Blockchain {
+    /// Holds the blockchain state lock while `f` runs, so that no block can be pushed meanwhile.
+    fn with_snapshot<T, F: FnOnce(&dyn SlotSnapshot) -> T>(&self, f: F) -> T {
+        let state = self.state();
+        f(&LockedBlockchain { blockchain: self, state: &state })
+    }
+}
+
+/// A `Blockchain` whose state lock is held, see `SlotResolver::with_snapshot`.
+struct LockedBlockchain<'a> {
+    blockchain: &'a Blockchain,
+    state: &'a BlockchainState,
+}
+
+impl<'a> SlotSnapshot for LockedBlockchain<'a> {
+    fn state_height(&self) -> u32 {
+        self.state.block_number()
+    }
+
+    fn slashed_set_for_epoch(&self, epoch: u32) -> Option<BitSet> {
+        slashed_set_of(self.state, epoch)
+    }
+
+    fn get_slot_at(&self, block_number: u32, view_number: u32) -> Option<(Slot, u16)> {
+        // This locks the state recursively, which succeeds while we hold the lock.
+        Blockchain::get_slot_at(self.blockchain, block_number, view_number, None)
+    }
+}
+
+/// Looks up the slot that produced both headers of `fork_proof` using `resolve`, which behaves
+/// like `SlotResolver::get_slot_at`.
+fn fork_proof_slot<F: FnMut(u32, u32) -> Option<(Slot, u16)>>(fork_proof: &ForkProof, mut resolve: F) -> Result<(Slot, u16), ForkProofPoolError> {
+    let (slot, slot_number) = resolve(fork_proof.block_number(), fork_proof.view_number())
+        .ok_or(ForkProofPoolError::UnexpectedBlock)?;
+
+    // Both headers must have been produced for the same slot.
+    if fork_proof.header1.block_number != fork_proof.header2.block_number
+        || fork_proof.header1.view_number != fork_proof.header2.view_number {
+        let slot_number2 = resolve(fork_proof.header2.block_number, fork_proof.header2.view_number)
+            .map(|(_, slot_number)| slot_number);
+        if slot_number2 != Some(slot_number) {
+            return Err(ForkProofPoolError::UnexpectedBlock);
+        }
+    }
+
+    Ok((slot, slot_number))
+}
+
+/// Returns the slashed set of the epoch `fork_proof` belongs to, if that epoch can still be
+/// slashed at `block_number`.
+fn slashed_set_for(snapshot: &dyn SlotSnapshot, block_number: u32, fork_proof: &ForkProof) -> Result<BitSet, ForkProofPoolError> {
+    // Only proofs for the current and the previous epoch can still be slashed.
+    let current_epoch = policy::epoch_at(block_number);
+    let proof_epoch = policy::epoch_at(fork_proof.block_number());
+    if proof_epoch != current_epoch && proof_epoch + 1 != current_epoch {
+        return Err(ForkProofPoolError::InvalidEpochTarget);
+    }
+    snapshot.slashed_set_for_epoch(proof_epoch)
+        .ok_or(ForkProofPoolError::InvalidEpochTarget)
+}
+
+/// Number of resolved slots `ForkProofPool` keeps cached.
+const SLOT_CACHE_SIZE: usize = 16;
+
+/// Small LRU cache of slots resolved through `SlotResolver::get_slot_at`, keyed by block number
+/// and view number. The most recently used entry is at the back.
+#[derive(Clone, Default)]
+struct SlotCache {
+    entries: VecDeque<((u32, u32), (Slot, u16))>,
+    hits: usize,
+}
+
+impl SlotCache {
+    /// Returns the cached slot for `key`, resolving and caching it with `resolve` if it isn't
+    /// cached yet.
+    fn get_or_resolve<F: FnOnce() -> Option<(Slot, u16)>>(&mut self, key: (u32, u32), resolve: F) -> Option<(Slot, u16)> {
+        if let Some(slot) = self.get(key) {
+            return Some(slot);
+        }
+        let slot = resolve()?;
+        self.insert(key, slot.clone());
+        Some(slot)
+    }
+
+    /// Like `get`, but doesn't count as a use of the entry.
+    fn peek(&self, key: (u32, u32)) -> Option<(Slot, u16)> {
+        self.entries.iter()
+            .find(|(entry_key, _)| *entry_key == key)
+            .map(|(_, slot)| slot.clone())
+    }
+
+    fn get(&mut self, key: (u32, u32)) -> Option<(Slot, u16)> {
+        let position = self.entries.iter().position(|(entry_key, _)| *entry_key == key)?;
+        let entry = self.entries.remove(position)?;
+        let slot = entry.1.clone();
+        self.entries.push_back(entry);
+        self.hits += 1;
+        Some(slot)
+    }
+
+    fn insert(&mut self, key: (u32, u32), slot: (Slot, u16)) {
+        if self.entries.len() >= SLOT_CACHE_SIZE {
+            self.entries.pop_front();
+        }
+        self.entries.push_back((key, slot));
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+pub struct ForkProofPool<R: SlotResolver = Blockchain> {
+    blockchain: Arc<R>,
+    fork_proofs: HashMap<Blake2bHash, PooledForkProof>,
+    /// Slot numbers for which a fork proof is pooled. At most one proof per slot is kept.
+    fork_proof_slots: HashSet<u16>,
+    max_proofs: usize,
+    /// Fork proofs against validators with fewer slots are rejected.
+    min_slot_weight: u16,
+    slot_cache: SlotCache,
+    listener: Option<ForkProofPoolListener>,
+
+    #[cfg(feature = "metrics")]
+    metrics: ForkProofPoolMetrics,
 }
 
-impl ForkProofPool {
-    pub fn new() -> Self {
-        Self::default()
+impl<R: SlotResolver> ForkProofPool<R> {
+    /// Default capacity of the pool. Since we only keep one proof per slot, we never need more.
+    pub const DEFAULT_CAPACITY: usize = policy::SLOTS as usize;
+
+    pub fn new(blockchain: Arc<R>) -> Self {
+        Self::with_capacity(blockchain, Self::DEFAULT_CAPACITY)
+    }
+
+    /// Creates a pool that holds at most `max_proofs` fork proofs.
+    pub fn with_capacity(blockchain: Arc<R>, max_proofs: usize) -> Self {
+        ForkProofPool {
+            blockchain,
+            fork_proofs: HashMap::new(),
+            fork_proof_slots: HashSet::new(),
+            max_proofs,
+            min_slot_weight: 0,
+            slot_cache: SlotCache::default(),
+            listener: None,
+            #[cfg(feature = "metrics")]
+            metrics: ForkProofPoolMetrics::default(),
+        }
     }
 
-    /// Adds a fork proof if it is not yet part of the pool.
-    /// Returns whether it has been added.
-    pub fn insert(&mut self, fork_proof: ForkProof) -> bool {
-        self.fork_proofs.insert(fork_proof)
+    #[cfg(feature = "metrics")]
+    pub fn metrics(&self) -> &ForkProofPoolMetrics {
+        &self.metrics
+    }
+
+    /// Maximum number of fork proofs this pool can hold.
+    pub fn capacity(&self) -> usize {
+        self.max_proofs
+    }
+
+    /// Minimum number of slots the offending validator must hold for a fork proof to be accepted.
+    pub fn min_slot_weight(&self) -> u16 {
+        self.min_slot_weight
+    }
+
+    /// Rejects fork proofs against validators holding fewer than `min_slot_weight` slots from now
+    /// on. Defaults to 0, which accepts all proofs. Proofs already in the pool are kept.
+    pub fn set_min_slot_weight(&mut self, min_slot_weight: u16) {
+        self.min_slot_weight = min_slot_weight;
+    }
+
+    /// Total serialized size of all pooled fork proofs, i.e. the block space needed to include
+    /// all of them.
+    pub fn total_serialized_size(&self) -> usize {
+        self.fork_proofs.values()
+            .map(|pooled| pooled.fork_proof.serialized_size())
+            .sum()
+    }
+
+    /// Sets the listener that is called whenever a fork proof is added to or removed from the
+    /// pool, replacing any previous listener.
+    pub fn set_listener(&mut self, listener: ForkProofPoolListener) {
+        self.listener = Some(listener);
+    }
+
+    fn notify(&self, event: ForkProofPoolEvent) {
+        if let Some(listener) = &self.listener {
+            listener(event);
+        }
+    }
+
+    /// Number of slot lookups that were answered from the slot cache.
+    pub fn slot_cache_hits(&self) -> usize {
+        self.slot_cache.hits
+    }
+
+    /// Number of fork proofs currently in the pool.
+    pub fn len(&self) -> usize {
+        self.fork_proofs.len()
+    }
+
+    /// Returns whether the pool contains no fork proofs.
+    pub fn is_empty(&self) -> bool {
+        self.fork_proofs.is_empty()
+    }
+
+    /// Adds a fork proof if it is not yet part of the pool and valid with respect to the current
+    /// blockchain state.
+    ///
+    /// The proof is checked like in `validate`. If the pool is full, the proof is only accepted if
+    /// it slashes a validator with more slots than the lowest-weighted proof in the pool, which is
+    /// then evicted. Among the lowest-weighted proofs, the one with the lowest view and block
+    /// number is evicted first.
+    pub fn insert(&mut self, fork_proof: ForkProof) -> Result<InsertOutcome, ForkProofPoolError> {
+        self.insert_with_timestamp(fork_proof, 0)
+    }
+
+    /// Like `insert`, but records `timestamp` as the time the proof entered the pool, see
+    /// `proof_age`. The pool doesn't read a clock itself, so the caller decides on the clock and
+    /// unit, e.g. milliseconds since the Unix epoch. Proofs added by other means than this method
+    /// have a timestamp of 0.
+    pub fn insert_with_timestamp(&mut self, fork_proof: ForkProof, timestamp: u64) -> Result<InsertOutcome, ForkProofPoolError> {
+        let blockchain = Arc::clone(&self.blockchain);
+        self.insert_at(&*blockchain, fork_proof, None, timestamp)
+            .map(|(outcome, _)| outcome)
+    }
+
+    /// Like `insert`, but also returns the fork proof that was evicted to make room for the new
+    /// one, if any. This allows relaying evicted proofs to other nodes before they are lost.
+    pub fn insert_evicting(&mut self, fork_proof: ForkProof) -> Result<(InsertOutcome, Option<ForkProof>), ForkProofPoolError> {
+        let blockchain = Arc::clone(&self.blockchain);
+        self.insert_at(&*blockchain, fork_proof, None, 0)
+    }
+
+    /// Like `insert`, but uses the given slot instead of looking it up, for callers that already
+    /// resolved it (e.g. while validating a block's fork proofs). The signature is still verified
+    /// and the slashed set is still checked.
+    ///
+    /// The caller is responsible for `slot` and `slot_number` being the slot that produced both
+    /// headers of the fork proof.
+    pub fn insert_with_slot(&mut self, fork_proof: ForkProof, slot: &Slot, slot_number: u16) -> Result<InsertOutcome, ForkProofPoolError> {
+        let blockchain = Arc::clone(&self.blockchain);
+        self.insert_at(&*blockchain, fork_proof, Some((slot.clone(), slot_number)), 0)
+            .map(|(outcome, _)| outcome)
+    }
+
+    /// Inserts multiple fork proofs, validating all of them against the same chain state, see
+    /// `SlotResolver::with_snapshot`. For `Blockchain`, the state lock is held for the whole batch.
+    ///
+    /// Returns the result of inserting each proof, in the same order as `fork_proofs`.
+    pub fn insert_batch(&mut self, fork_proofs: Vec<ForkProof>) -> Vec<Result<InsertOutcome, ForkProofPoolError>> {
+        let blockchain = Arc::clone(&self.blockchain);
+        blockchain.with_snapshot(|snapshot| {
+            fork_proofs.into_iter()
+                .map(|fork_proof| self.insert_at(snapshot, fork_proof, None, 0).map(|(outcome, _)| outcome))
+                .collect()
+        })
+    }
+
+    /// Inserts all fork proofs of `other` like `insert_batch` does, so duplicates, occupied and
+    /// slashed slots are handled like for any other proof. Added proofs keep the timestamp they
+    /// were inserted into `other` with.
+    ///
+    /// Both pools must use the same blockchain, otherwise the proofs of `other` would be
+    /// validated against a different chain state than the one they were accepted for.
+    pub fn merge(&mut self, other: ForkProofPool<R>) -> MergeSummary {
+        debug_assert!(Arc::ptr_eq(&self.blockchain, &other.blockchain), "Merged fork proof pools must share the blockchain");
+
+        let blockchain = Arc::clone(&self.blockchain);
+        blockchain.with_snapshot(|snapshot| {
+            let mut summary = MergeSummary::default();
+            for (_, pooled) in other.fork_proofs {
+                match self.insert_at(snapshot, pooled.fork_proof, None, pooled.inserted_at) {
+                    Ok(InsertOutcome::Added) => summary.added += 1,
+                    Ok(InsertOutcome::AlreadyKnown) | Ok(InsertOutcome::SlotOccupied) => summary.ignored += 1,
+                    Err(_) => summary.rejected += 1,
+                }
+            }
+            summary
+        })
+    }
+
+    fn insert_at(&mut self, snapshot: &dyn SlotSnapshot, fork_proof: ForkProof, slot: Option<(Slot, u16)>, inserted_at: u64) -> Result<(InsertOutcome, Option<ForkProof>), ForkProofPoolError> {
+        let result = self.try_insert(snapshot, fork_proof, slot, inserted_at);
+        #[cfg(feature = "metrics")]
+        self.metrics.note_insert(&result.as_ref().map(|(outcome, _)| *outcome).map_err(Clone::clone));
+        result
+    }
+
+    /// Validates and inserts a fork proof against the chain state of `snapshot`. If `slot` is
+    /// `None`, it is resolved from the headers. Returns the outcome and the fork proof that was
+    /// evicted to make room, if any.
+    fn try_insert(&mut self, snapshot: &dyn SlotSnapshot, fork_proof: ForkProof, slot: Option<(Slot, u16)>, inserted_at: u64) -> Result<(InsertOutcome, Option<ForkProof>), ForkProofPoolError> {
+        // Proofs with swapped headers describe the same offense, so only store them once.
+        let fork_proof = fork_proof.canonical();
+        let hash: Blake2bHash = fork_proof.hash();
+        if self.fork_proofs.contains_key(&hash) {
+            trace!("Fork proof {} is already known", hash);
+            return Ok((InsertOutcome::AlreadyKnown, None));
+        }
+
+        let block_number = snapshot.state_height();
+        let epoch = policy::epoch_at(fork_proof.block_number());
+        let reject = |e: ForkProofPoolError| {
+            debug!("Rejecting fork proof {} for block #{}.{} (epoch {}) at block #{}: {}",
+                   hash, fork_proof.block_number(), fork_proof.view_number(), epoch, block_number, e);
+            e
+        };
+
+        let slashed_set = slashed_set_for(snapshot, block_number, &fork_proof).map_err(reject)?;
+        let (slot, slot_number) = match slot {
+            Some(slot) => slot,
+            None => {
+                let slot_cache = &mut self.slot_cache;
+                fork_proof_slot(&fork_proof, |block_number, view_number| {
+                    slot_cache.get_or_resolve((block_number, view_number), || snapshot.get_slot_at(block_number, view_number))
+                }).map_err(reject)?
+            },
+        };
+        let reject_slot = |e: ForkProofPoolError| {
+            debug!("Rejecting fork proof {} for slot {} (epoch {}): {}", hash, slot_number, epoch, e);
+            e
+        };
+        self.check_slot(&fork_proof, &slashed_set, &slot, slot_number).map_err(reject_slot)?;
+
+        if self.fork_proof_slots.contains(&slot_number) {
+            trace!("Slot {} of fork proof {} is already occupied", slot_number, hash);
+            return Ok((InsertOutcome::SlotOccupied, None));
+        }
+
+        let validator = slot.public_key().compressed().clone();
+        let weight = slot.validator_slot.num_slots();
+        let evicted = if self.fork_proofs.len() >= self.max_proofs {
+            Some(self.evict_for(weight).map_err(reject_slot)?)
+        } else {
+            None
+        };
+
+        trace!("Adding fork proof {} for slot {} (epoch {})", hash, slot_number, epoch);
+        self.fork_proof_slots.insert(slot_number);
+        self.fork_proofs.insert(hash.clone(), PooledForkProof { fork_proof, slot_number, validator, weight, inserted_at });
+        self.notify(ForkProofPoolEvent::Added(hash, slot_number));
+        Ok((InsertOutcome::Added, evicted))
+    }
+
+    /// Checks whether `fork_proof` would be accepted by `insert`, i.e. whether it targets an
+    /// epoch that can still be slashed, its slot hasn't been slashed yet, the offending validator
+    /// has at least `min_slot_weight` slots and the signatures are valid. The pool isn't
+    /// modified, so this can be used to decide whether to relay a proof.
+    ///
+    /// Returns the number of the slot the proof slashes. Note that `insert` might still not add
+    /// the proof if the pool already contains a proof for that slot.
+    pub fn validate(&self, fork_proof: &ForkProof) -> Result<u16, ForkProofPoolError> {
+        let block_number = self.blockchain.state_height();
+        let slashed_set = slashed_set_for(&*self.blockchain, block_number, fork_proof)?;
+        let (slot, slot_number) = fork_proof_slot(fork_proof, |block_number, view_number| {
+            self.slot_cache.peek((block_number, view_number))
+                .or_else(|| self.blockchain.get_slot_at(block_number, view_number))
+        })?;
+        self.check_slot(fork_proof, &slashed_set, &slot, slot_number)?;
+        Ok(slot_number)
+    }
+
+    /// Checks that the slot hasn't been slashed yet, that it is heavy enough and that it signed
+    /// both headers of `fork_proof`.
+    fn check_slot(&self, fork_proof: &ForkProof, slashed_set: &BitSet, slot: &Slot, slot_number: u16) -> Result<(), ForkProofPoolError> {
+        if slashed_set.contains(slot_number as usize) {
+            return Err(ForkProofPoolError::SlotAlreadySlashed);
+        }
+        if slot.validator_slot.num_slots() < self.min_slot_weight {
+            return Err(ForkProofPoolError::InsufficientWeight);
+        }
+        fork_proof.verify(&slot.public_key().uncompress_unchecked())?;
+        Ok(())
+    }
+
+    /// Makes room for a proof of the given weight by evicting the proof with the lowest
+    /// `ForkProof::eviction_priority`, i.e. the most stale of the lowest-weighted proofs, if that
+    /// one weighs less. Returns the evicted proof.
+    fn evict_for(&mut self, weight: u16) -> Result<ForkProof, ForkProofPoolError> {
+        let lowest = self.fork_proofs.iter()
+            .min_by_key(|(_, pooled)| pooled.fork_proof.eviction_priority(pooled.weight))
+            .filter(|(_, pooled)| pooled.weight < weight)
+            .map(|(hash, _)| hash.clone())
+            .ok_or(ForkProofPoolError::PoolFull)?;
+
+        let evicted = self.remove(&lowest).expect("Lowest-weighted fork proof must be in the pool");
+        #[cfg(feature = "metrics")]
+        self.metrics.note_evicted_fork_proof();
+        Ok(evicted)
+    }
+
+    /// Removes a fork proof by its hash and frees its slot, so that another proof for the same
+    /// slot can be inserted. Returns the removed proof, if it was in the pool.
+    pub fn remove(&mut self, hash: &Blake2bHash) -> Option<ForkProof> {
+        let pooled = self.fork_proofs.remove(hash)?;
+        self.fork_proof_slots.remove(&pooled.slot_number);
+        self.notify(ForkProofPoolEvent::Removed(hash.clone(), pooled.slot_number));
+        Some(pooled.fork_proof)
+    }
+
+    /// Removes all fork proofs, e.g. on a resync, notifying the listener about each of them. The
+    /// pool can be used again right away.
+    pub fn clear(&mut self) {
+        let hashes: Vec<Blake2bHash> = self.fork_proofs.keys().cloned().collect();
+        for hash in hashes {
+            self.remove(&hash);
+        }
+        self.slot_cache.clear();
+    }
+
+    /// Iterates over all pooled fork proofs in arbitrary order.
+    pub fn iter(&self) -> impl Iterator<Item = &ForkProof> {
+        self.fork_proofs.values().map(|pooled| &pooled.fork_proof)
+    }
+
+    /// Iterates over all pooled fork proofs together with the slot number they slash.
+    pub fn iter_with_slots(&self) -> impl Iterator<Item = (&ForkProof, u16)> {
+        self.fork_proofs.values().map(|pooled| (&pooled.fork_proof, pooled.slot_number))
+    }
+
+    /// Returns the pooled fork proofs for blocks in the given epoch.
+    pub fn proofs_for_epoch(&self, epoch: u32) -> Vec<&ForkProof> {
+        self.iter()
+            .filter(|fork_proof| policy::epoch_at(fork_proof.block_number()) == epoch)
+            .collect()
+    }
+
+    /// Slot numbers the pooled fork proofs slash, e.g. to cross-check them against the slashed
+    /// set on chain.
+    pub fn slashable_slots(&self) -> &HashSet<u16> {
+        &self.fork_proof_slots
+    }
+
+    /// The total number of slots at risk of being slashed by the pooled fork proofs, i.e. the sum of
+    /// the slot counts of the accused validators as resolved on insert. A validator with proofs
+    /// in several of its slots loses its slots only once, so it is only counted once.
+    pub fn pending_slash_weight(&self) -> u16 {
+        let weights: BTreeMap<&CompressedPublicKey, u16> = self.fork_proofs.values()
+            .map(|pooled| (&pooled.validator, pooled.weight))
+            .collect();
+        weights.values()
+            .fold(0u16, |total, weight| total.saturating_add(*weight))
+    }
+
+    /// Returns the slot numbers that have a pooled fork proof, in ascending order.
+    fn sorted_slots(&self) -> Vec<u16> {
+        let mut slots: Vec<u16> = self.fork_proof_slots.iter().cloned().collect();
+        slots.sort_unstable();
+        slots
+    }
+
+    /// Returns a summary of every pooled fork proof, ordered by block number, view number and
+    /// slot number. Meant for observability; use `get_fork_proofs_for_block` to build blocks.
+    pub fn report(&self) -> Vec<ForkProofReport> {
+        let mut reports: Vec<ForkProofReport> = self.fork_proofs.iter()
+            .map(|(hash, pooled)| ForkProofReport {
+                hash: hash.clone(),
+                slot_number: pooled.slot_number,
+                block_number: pooled.fork_proof.block_number(),
+                view_number: pooled.fork_proof.view_number(),
+                epoch: policy::epoch_at(pooled.fork_proof.block_number()),
+            })
+            .collect();
+        reports.sort_by_key(|report| (report.block_number, report.view_number, report.slot_number));
+        reports
+    }
+
+    /// The pooled fork proof for the earliest block, or `None` if the pool is empty. Ties are
+    /// broken by view number, then by hash. Together with `newest_proof`, this shows whether
+    /// pooled proofs get included in blocks or linger until their epoch can't be slashed anymore.
+    pub fn oldest_proof(&self) -> Option<&ForkProof> {
+        self.fork_proofs.iter()
+            .min_by_key(|(hash, pooled)| (pooled.fork_proof.block_number(), pooled.fork_proof.view_number(), *hash))
+            .map(|(_, pooled)| &pooled.fork_proof)
+    }
+
+    /// The pooled fork proof for the latest block, or `None` if the pool is empty. Ties are broken
+    /// like in `oldest_proof`.
+    pub fn newest_proof(&self) -> Option<&ForkProof> {
+        self.fork_proofs.iter()
+            .max_by_key(|(hash, pooled)| (pooled.fork_proof.block_number(), pooled.fork_proof.view_number(), *hash))
+            .map(|(_, pooled)| &pooled.fork_proof)
+    }
+
+    /// Public key of the validator that the pooled fork proof with the given hash would slash.
+    ///
+    /// Returns `None` if the proof isn't pooled or its slot can't be resolved anymore.
+    pub fn accused_validator(&self, fork_proof_hash: &Blake2bHash) -> Option<PublicKey> {
+        let pooled = self.fork_proofs.get(fork_proof_hash)?;
+        let slot = self.resolve_pooled(pooled)?;
+        let public_key = slot.public_key().uncompress()?;
+        Some(*public_key)
+    }
+
+    /// Whether the pool contains a fork proof against the validator with `public_key`, e.g. to
+    /// avoid relaying a redundant proof against them.
+    ///
+    /// This resolves the slot of every pooled proof, so it takes linear time and may need a
+    /// blockchain lookup per proof. Use `contains_slot` if the slot number is known.
+    pub fn contains_for_validator(&self, public_key: &PublicKey) -> bool {
+        let compressed = public_key.compress();
+        self.fork_proofs.values()
+            .filter_map(|pooled| self.resolve_pooled(pooled))
+            .any(|slot| *slot.public_key().compressed() == compressed)
+    }
+
+    /// Resolves the slot of a pooled fork proof, or returns `None` if it can't be resolved or
+    /// doesn't match the slot number the proof was pooled with anymore.
+    fn resolve_pooled(&self, pooled: &PooledForkProof) -> Option<Slot> {
+        let key = (pooled.fork_proof.block_number(), pooled.fork_proof.view_number());
+        let (slot, slot_number) = self.slot_cache.peek(key)
+            .or_else(|| self.blockchain.get_slot_at(key.0, key.1))?;
+        if slot_number != pooled.slot_number {
+            return None;
+        }
+        Some(slot)
+    }
+
+    /// Time the pooled fork proof with the given hash has spent in the pool at `now`, given in the
+    /// same clock and unit as the timestamp passed to `insert_with_timestamp`. Returns `None` if
+    /// the proof isn't pooled, and 0 if `now` is before the insertion.
+    pub fn proof_age(&self, hash: &Blake2bHash, now: u64) -> Option<u64> {
+        let pooled = self.fork_proofs.get(hash)?;
+        Some(now.saturating_sub(pooled.inserted_at))
     }
 
     /// Checks whether a fork proof is already part of the pool.
     pub fn contains(&self, fork_proof: &ForkProof) -> bool {
-        self.fork_proofs.contains(fork_proof)
+        self.fork_proofs.contains_key(&fork_proof.canonical_hash())
+    }
+
+    /// Checks whether a fork proof for the given slot number is part of the pool.
+    pub fn contains_slot(&self, slot_number: u16) -> bool {
+        self.fork_proof_slots.contains(&slot_number)
     }
 
     /// Applies a block to the pool, removing processed fork proofs.
     pub fn apply_block(&mut self, block: &Block) {
+        self.slot_cache.clear();
         if let Block::Micro(MicroBlock { extrinsics: Some(extrinsics), .. }) = block {
             for fork_proof in extrinsics.fork_proofs.iter() {
-                self.fork_proofs.remove(fork_proof);
+                if self.remove(&fork_proof.canonical_hash()).is_some() {
+                    #[cfg(feature = "metrics")]
+                    self.metrics.note_applied_fork_proof();
+                }
             }
         }
     }
 
     /// Reverts a block, re-adding fork proofs.
+    ///
+    /// This resolves slots through the `SlotResolver`, which for `Blockchain` acquires the
+    /// blockchain state lock. Callers that already hold a blockchain lock must use
+    /// `revert_block_with` instead.
     pub fn revert_block(&mut self, block: &Block) {
+        let blockchain = Arc::clone(&self.blockchain);
+        self.revert_block_with(block, |block_number, view_number| {
+            blockchain.get_slot_at(block_number, view_number)
+        });
+    }
+
+    /// Reverts a block, re-adding fork proofs. Slots are looked up with `resolver`, which is
+    /// given the block number and view number of a fork proof and should behave like
+    /// `SlotResolver::get_slot_at`.
+    ///
+    /// Like `insert`, this keeps at most one proof per slot and respects the capacity of the
+    /// pool: proofs whose slot is already occupied are skipped, and a full pool only takes a
+    /// proof if it can evict a lower-weighted one.
+    pub fn revert_block_with(&mut self, block: &Block, resolver: impl Fn(u32, u32) -> Option<(Slot, u16)>) {
+        self.slot_cache.clear();
         if let Block::Micro(MicroBlock { extrinsics: Some(extrinsics), .. }) = block {
             for fork_proof in extrinsics.fork_proofs.iter() {
-                self.fork_proofs.insert(fork_proof.clone());
+                // Proofs that are still pooled don't need to be resolved and copied again.
+                let hash = fork_proof.canonical_hash();
+                if self.fork_proofs.contains_key(&hash) {
+                    continue;
+                }
+                let (slot, slot_number) = match resolver(fork_proof.block_number(), fork_proof.view_number()) {
+                    Some(resolved) => resolved,
+                    None => continue,
+                };
+                if self.fork_proof_slots.contains(&slot_number) {
+                    trace!("Not re-adding fork proof {}, slot {} is already occupied", hash, slot_number);
+                    continue;
+                }
+
+                let weight = slot.validator_slot.num_slots();
+                if self.fork_proofs.len() >= self.max_proofs {
+                    if let Err(e) = self.evict_for(weight) {
+                        debug!("Not re-adding fork proof {} for slot {}: {}", hash, slot_number, e);
+                        continue;
+                    }
+                }
+
+                self.fork_proof_slots.insert(slot_number);
+                self.fork_proofs.insert(hash.clone(), PooledForkProof {
+                    fork_proof: fork_proof.clone().canonical(),
+                    slot_number,
+                    validator: slot.public_key().compressed().clone(),
+                    weight,
+                    inserted_at: 0,
+                });
+                self.notify(ForkProofPoolEvent::Added(hash, slot_number));
+            }
+        }
+    }
+
+    /// Serializes the pooled fork proofs, e.g. to persist them across restarts.
+    ///
+    /// Only the proofs themselves are serialized, the blockchain handle and slot information
+    /// are not.
+    pub fn save_to_vec(&self) -> Vec<u8> {
+        let proofs: Vec<ForkProof> = self.iter().cloned().collect();
+        SerializeWithLength::serialize_to_vec::<u32>(&proofs)
+    }
+
+    /// Inserts fork proofs serialized with `save_to_vec`. Every proof goes through `insert`, so
+    /// proofs that became invalid in the meantime are dropped.
+    ///
+    /// Returns the number of proofs that have been added.
+    pub fn load_from_vec(&mut self, data: &[u8]) -> Result<usize, SerializingError> {
+        let proofs: Vec<ForkProof> = DeserializeWithLength::deserialize_from_vec::<u32>(data)?;
+
+        let mut num_added = 0;
+        for fork_proof in proofs {
+            if let Ok(InsertOutcome::Added) = self.insert(fork_proof) {
+                num_added += 1;
+            }
+        }
+        Ok(num_added)
+    }
+
+    /// Removes fork proofs that can't be included anymore at `block_number`, either because their
+    /// epoch can't be slashed anymore or because their slot has been slashed already.
+    ///
+    /// Returns the number of removed fork proofs.
+    pub fn housekeeping(&mut self, block_number: u32, current_slashed_set: &BitSet, previous_slashed_set: &BitSet) -> usize {
+        let current_epoch = policy::epoch_at(block_number);
+        self.prune(block_number, |epoch| {
+            if epoch == current_epoch {
+                Some(current_slashed_set)
+            } else {
+                Some(previous_slashed_set)
+            }
+        })
+    }
+
+    /// Like calling `housekeeping` for every block up to `up_to_block`, e.g. after catching up
+    /// with many blocks, but iterates over the pool only once.
+    ///
+    /// `slashed_sets` maps epoch numbers to the union of the slashed sets of that epoch over the
+    /// range of blocks. Since the slashed set of an epoch only grows, this is its slashed set at
+    /// `up_to_block`. Epochs without an entry are treated as not slashed at all. Proofs for epochs
+    /// that can't be slashed anymore at `up_to_block` are removed regardless of `slashed_sets`.
+    ///
+    /// Returns the number of removed fork proofs.
+    pub fn housekeeping_range(&mut self, up_to_block: u32, slashed_sets: &HashMap<u32, BitSet>) -> usize {
+        self.prune(up_to_block, |epoch| slashed_sets.get(&epoch))
+    }
+
+    /// Removes fork proofs whose epoch can't be slashed anymore at `block_number` or whose slot
+    /// is in the slashed set `slashed_set_for` returns for their epoch.
+    fn prune<'a, F: Fn(u32) -> Option<&'a BitSet>>(&mut self, block_number: u32, slashed_set_for: F) -> usize {
+        let current_epoch = policy::epoch_at(block_number);
+
+        let stale: Vec<Blake2bHash> = self.fork_proofs.iter()
+            .filter(|(_, pooled)| {
+                let proof_epoch = policy::epoch_at(pooled.fork_proof.block_number());
+                if proof_epoch != current_epoch && proof_epoch + 1 != current_epoch {
+                    return true;
+                }
+                slashed_set_for(proof_epoch)
+                    .map_or(false, |slashed_set| slashed_set.contains(pooled.slot_number as usize))
+            })
+            .map(|(hash, _)| hash.clone())
+            .collect();
+
+        let num_removed = stale.len();
+        for hash in stale {
+            self.remove(&hash);
+        }
+        #[cfg(feature = "metrics")]
+        self.metrics.note_pruned_fork_proofs(num_removed);
+        num_removed
+    }
+
+    /// Removes all fork proofs for blocks whose epoch is not in `keep`, regardless of the slashed
+    /// sets. This is a coarser cleanup than `housekeeping`, e.g. after a reorg across epochs.
+    ///
+    /// Returns the number of removed fork proofs.
+    pub fn retain_epochs(&mut self, keep: &[u32]) -> usize {
+        let stale: Vec<Blake2bHash> = self.fork_proofs.iter()
+            .filter(|(_, pooled)| !keep.contains(&policy::epoch_at(pooled.fork_proof.block_number())))
+            .map(|(hash, _)| hash.clone())
+            .collect();
+
+        let num_removed = stale.len();
+        for hash in stale {
+            self.remove(&hash);
+        }
+        num_removed
+    }
+
+    /// Re-resolves the slot of every pooled fork proof against the current blockchain state and
+    /// checks it like `insert` does, e.g. at epoch boundaries when the validator set changed.
+    /// Proofs that don't validate anymore or whose slot can't be resolved are removed, the others
+    /// are updated with their current slot number, validator and weight. If several proofs now
+    /// slash the same slot, only the one with the lowest hash is kept.
+    ///
+    /// Returns the hashes of the removed fork proofs.
+    pub fn revalidate(&mut self) -> Vec<Blake2bHash> {
+        self.slot_cache.clear();
+        let block_number = self.blockchain.state_height();
+
+        let mut hashes: Vec<Blake2bHash> = self.fork_proofs.keys().cloned().collect();
+        hashes.sort();
+
+        let mut slots = HashSet::new();
+        let mut updated = Vec::new();
+        let mut dropped = Vec::new();
+        for hash in hashes {
+            let fork_proof = &self.fork_proofs[&hash].fork_proof;
+            let result = slashed_set_for(&*self.blockchain, block_number, fork_proof)
+                .and_then(|slashed_set| {
+                    let (slot, slot_number) = fork_proof_slot(fork_proof, |block_number, view_number| {
+                        self.blockchain.get_slot_at(block_number, view_number)
+                    })?;
+                    self.check_slot(fork_proof, &slashed_set, &slot, slot_number)?;
+                    Ok((slot_number, slot.public_key().compressed().clone(), slot.validator_slot.num_slots()))
+                });
+            match result {
+                Ok((slot_number, validator, weight)) if slots.insert(slot_number) => {
+                    updated.push((hash, slot_number, validator, weight))
+                },
+                _ => dropped.push(hash),
+            }
+        }
+
+        for hash in dropped.iter() {
+            self.remove(hash);
+        }
+        for (hash, slot_number, validator, weight) in updated {
+            let pooled = self.fork_proofs.get_mut(&hash).expect("Revalidated fork proof must be in the pool");
+            let previous_slot_number = pooled.slot_number;
+            pooled.slot_number = slot_number;
+            pooled.validator = validator;
+            pooled.weight = weight;
+            if previous_slot_number != slot_number {
+                self.notify(ForkProofPoolEvent::Removed(hash.clone(), previous_slot_number));
+                self.notify(ForkProofPoolEvent::Added(hash, slot_number));
             }
         }
+        self.fork_proof_slots = slots;
+        dropped
+    }
+
+    /// Like `housekeeping`, but looks up the slashed sets for the epoch of `block_number` and the
+    /// one before through the `SlotResolver`. Slashed sets that aren't available are treated as
+    /// empty.
+    pub fn housekeeping_from_chain(&mut self, block_number: u32) -> usize {
+        let epoch = policy::epoch_at(block_number);
+        let current_slashed_set = self.blockchain.slashed_set_for_epoch(epoch)
+            .unwrap_or_default();
+        let previous_slashed_set = epoch.checked_sub(1)
+            .and_then(|epoch| self.blockchain.slashed_set_for_epoch(epoch))
+            .unwrap_or_default();
+        self.housekeeping(block_number, &current_slashed_set, &previous_slashed_set)
+    }
+
+    /// Returns copies of all pooled fork proofs, ordered by `(block_number, view_number,
+    /// slot_number)` like `get_fork_proofs_for_block`, but without a size limit, e.g. to report
+    /// them. Use `iter` to avoid copying them.
+    pub fn get_all(&self) -> Vec<ForkProof> {
+        self.sorted_pooled().into_iter()
+            .map(|(_, pooled)| pooled.fork_proof.clone())
+            .collect()
     }
 
-    /// Returns a list of current fork proofs.
+    /// Returns a list of current fork proofs whose total serialized size is at most `max_size`.
+    ///
+    /// Proofs are considered in order of `(block_number, view_number, slot_number)`, so the
+    /// selection only depends on the pool contents.
     pub fn get_fork_proofs_for_block(&self, max_size: usize) -> Vec<ForkProof> {
-        let mut proofs = Vec::new();
+        self.preview_for_block(max_size).into_iter()
+            .map(|(hash, _)| self.fork_proofs[&hash].fork_proof.clone())
+            .collect()
+    }
+
+    /// Like `get_fork_proofs_for_block`, but also removes the selected proofs from the pool and
+    /// frees their slots, so they can't be selected again. If the block isn't produced after all,
+    /// the proofs should be re-inserted.
+    pub fn drain_for_block(&mut self, max_size: usize) -> Vec<ForkProof> {
+        self.preview_for_block(max_size).into_iter()
+            .filter_map(|(hash, _)| self.remove(&hash))
+            .collect()
+    }
+
+    /// Returns the hashes and serialized sizes of the fork proofs `get_fork_proofs_for_block`
+    /// would select, in the same order, without copying the proofs. This can be used to plan the
+    /// space of a block or to log the selection before materializing it.
+    pub fn preview_for_block(&self, max_size: usize) -> Vec<(Blake2bHash, usize)> {
+        let mut selected = Vec::new();
         let mut size = 0;
-        for proof in self.fork_proofs.iter() {
-            if size + proof.serialized_size() < max_size {
-                proofs.push(proof.clone());
-                size += proof.serialized_size();
+        for (hash, pooled) in self.sorted_pooled() {
+            let proof_size = pooled.fork_proof.serialized_size();
+            if size + proof_size > max_size {
+                // Skip this one, a later proof might still fit.
+                continue;
             }
+            selected.push((hash.clone(), proof_size));
+            size += proof_size;
         }
-        proofs
+        selected
+    }
+
+    /// Returns the pooled fork proofs with their hashes, ordered by
+    /// `(block_number, view_number, slot_number)`.
+    fn sorted_pooled(&self) -> Vec<(&Blake2bHash, &PooledForkProof)> {
+        let mut sorted: Vec<(&Blake2bHash, &PooledForkProof)> = self.fork_proofs.iter().collect();
+        sorted.sort_by_key(|(_, pooled)| (pooled.fork_proof.block_number(), pooled.fork_proof.view_number(), pooled.slot_number));
+        sorted
+    }
+}
+
+/// Clones the pooled fork proofs and settings. The clone shares the `SlotResolver` (i.e. the
+/// blockchain) with the original pool and starts with fresh metrics and without a listener.
+impl<R: SlotResolver> Clone for ForkProofPool<R> {
+    fn clone(&self) -> Self {
+        ForkProofPool {
+            blockchain: Arc::clone(&self.blockchain),
+            fork_proofs: self.fork_proofs.clone(),
+            fork_proof_slots: self.fork_proof_slots.clone(),
+            max_proofs: self.max_proofs,
+            min_slot_weight: self.min_slot_weight,
+            slot_cache: self.slot_cache.clone(),
+            listener: None,
+            #[cfg(feature = "metrics")]
+            metrics: ForkProofPoolMetrics::default(),
+        }
+    }
+}
+
+impl<R: SlotResolver> fmt::Debug for ForkProofPool<R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "ForkProofPool {{ num_proofs: {}, slots: {:?} }}", self.fork_proofs.len(), self.sorted_slots())
     }
 }
@@ -0,0 +1,104 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::slash::{ForkProofPoolError, InsertOutcome};
+
+
+#[derive(Default)]
+pub struct ForkProofPoolMetrics {
+    fork_proof_added_count: AtomicUsize,
+    fork_proof_invalid_epoch_target_count: AtomicUsize,
+    fork_proof_unexpected_block_count: AtomicUsize,
+    fork_proof_slot_already_slashed_count: AtomicUsize,
+    fork_proof_invalid_count: AtomicUsize,
+    fork_proof_pool_full_count: AtomicUsize,
+    fork_proof_insufficient_weight_count: AtomicUsize,
+    fork_proof_evicted_count: AtomicUsize,
+    fork_proof_applied_count: AtomicUsize,
+    fork_proof_pruned_count: AtomicUsize,
+}
+
+impl ForkProofPoolMetrics {
+    #[inline]
+    pub fn note_insert(&self, result: &Result<InsertOutcome, ForkProofPoolError>) {
+        match result {
+            Ok(InsertOutcome::Added) => self.note_added_fork_proof(),
+            Ok(_) => {},
+            Err(ForkProofPoolError::InvalidEpochTarget) => { self.fork_proof_invalid_epoch_target_count.fetch_add(1, Ordering::Release); },
+            Err(ForkProofPoolError::UnexpectedBlock) => { self.fork_proof_unexpected_block_count.fetch_add(1, Ordering::Release); },
+            Err(ForkProofPoolError::SlotAlreadySlashed) => { self.fork_proof_slot_already_slashed_count.fetch_add(1, Ordering::Release); },
+            Err(ForkProofPoolError::InvalidForkProof(_)) => { self.fork_proof_invalid_count.fetch_add(1, Ordering::Release); },
+            Err(ForkProofPoolError::PoolFull) => { self.fork_proof_pool_full_count.fetch_add(1, Ordering::Release); },
+            Err(ForkProofPoolError::InsufficientWeight) => { self.fork_proof_insufficient_weight_count.fetch_add(1, Ordering::Release); },
+        }
+    }
+
+    #[inline]
+    pub fn note_added_fork_proof(&self) {
+        self.fork_proof_added_count.fetch_add(1, Ordering::Release);
+    }
+
+    #[inline]
+    pub fn fork_proof_added_count(&self) -> usize {
+        self.fork_proof_added_count.load(Ordering::Acquire)
+    }
+
+    #[inline]
+    pub fn fork_proof_invalid_epoch_target_count(&self) -> usize {
+        self.fork_proof_invalid_epoch_target_count.load(Ordering::Acquire)
+    }
+
+    #[inline]
+    pub fn fork_proof_unexpected_block_count(&self) -> usize {
+        self.fork_proof_unexpected_block_count.load(Ordering::Acquire)
+    }
+
+    #[inline]
+    pub fn fork_proof_slot_already_slashed_count(&self) -> usize {
+        self.fork_proof_slot_already_slashed_count.load(Ordering::Acquire)
+    }
+
+    #[inline]
+    pub fn fork_proof_invalid_count(&self) -> usize {
+        self.fork_proof_invalid_count.load(Ordering::Acquire)
+    }
+
+    #[inline]
+    pub fn fork_proof_pool_full_count(&self) -> usize {
+        self.fork_proof_pool_full_count.load(Ordering::Acquire)
+    }
+
+    #[inline]
+    pub fn fork_proof_insufficient_weight_count(&self) -> usize {
+        self.fork_proof_insufficient_weight_count.load(Ordering::Acquire)
+    }
+
+    #[inline]
+    pub fn note_evicted_fork_proof(&self) {
+        self.fork_proof_evicted_count.fetch_add(1, Ordering::Release);
+    }
+
+    #[inline]
+    pub fn fork_proof_evicted_count(&self) -> usize {
+        self.fork_proof_evicted_count.load(Ordering::Acquire)
+    }
+
+    #[inline]
+    pub fn note_applied_fork_proof(&self) {
+        self.fork_proof_applied_count.fetch_add(1, Ordering::Release);
+    }
+
+    #[inline]
+    pub fn fork_proof_applied_count(&self) -> usize {
+        self.fork_proof_applied_count.load(Ordering::Acquire)
+    }
+
+    #[inline]
+    pub fn note_pruned_fork_proofs(&self, count: usize) {
+        self.fork_proof_pruned_count.fetch_add(count, Ordering::Release);
+    }
+
+    #[inline]
+    pub fn fork_proof_pruned_count(&self) -> usize {
+        self.fork_proof_pruned_count.load(Ordering::Acquire)
+    }
+}
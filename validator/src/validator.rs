@@ -124,7 +124,7 @@ impl Validator {
                 pk_idx: None,
                 slots: None,
                 status: ValidatorStatus::None,
-                fork_proof_pool: ForkProofPool::new(),
+                fork_proof_pool: ForkProofPool::new(Arc::clone(&consensus.blockchain)),
                 view_number,
                 active_view_change: None,
                 proposed_extrinsics: HashMap::new(),
@@ -199,6 +199,12 @@ impl Validator {
         }
     }
 
+    /// The number of slots the fork proofs in our pool could slash, see
+    /// `ForkProofPool::pending_slash_weight`.
+    pub fn pending_slash_weight(&self) -> u16 {
+        self.state.read().fork_proof_pool.pending_slash_weight()
+    }
+
     pub fn on_consensus_lost(&self) {
         trace!("Consensus lost");
         let mut state = self.state.write();
@@ -285,6 +291,11 @@ impl Validator {
                 self.validator_network.reset_epoch(None);
             },
         }
+
+        // The validator set might have changed, so pooled fork proofs might not verify anymore.
+        for hash in state.fork_proof_pool.revalidate() {
+            debug!("Dropped fork proof {} after epoch change", hash);
+        }
     }
 
     // Sets the state according to the information on the block
@@ -293,6 +304,7 @@ impl Validator {
 
         let mut state = self.state.write();
         state.fork_proof_pool.apply_block(&block);
+        self.fork_proof_housekeeping(&mut state);
     }
 
     // Sets the state according to the rebranch
@@ -304,6 +316,16 @@ impl Validator {
         for (_hash, block) in new_chain.iter() {
             state.fork_proof_pool.apply_block(&block);
         }
+        self.fork_proof_housekeeping(&mut state);
+    }
+
+    // Removes fork proofs from the pool that can't be included in the next block anymore
+    fn fork_proof_housekeeping(&self, state: &mut ValidatorState) {
+        let block_number = self.blockchain.block_number();
+        let num_removed = state.fork_proof_pool.housekeeping_from_chain(block_number);
+        if num_removed > 0 {
+            debug!("Removed {} stale fork proofs at block #{}", num_removed, block_number);
+        }
     }
 
     fn on_validator_network_event(&self, event: &ValidatorNetworkEvent) {
@@ -340,7 +362,9 @@ impl Validator {
     }
 
     fn on_fork_proof(&self, fork_proof: &ForkProof) {
-        self.state.write().fork_proof_pool.insert(fork_proof.clone());
+        if let Err(e) = self.state.write().fork_proof_pool.insert(fork_proof.clone()) {
+            debug!("Rejecting fork proof: {}", e);
+        }
     }
 
     pub fn on_slot_change(&self, slot_change: SlotChange) {
@@ -0,0 +1,946 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use beserial::{Deserialize, Serialize};
+use nimiq_block_albatross::{Block, ForkProof, ForkProofError, MicroBlock};
+use nimiq_block_production_albatross::BlockProducer;
+use nimiq_blockchain_albatross::blockchain::{Blockchain, PushResult};
+use nimiq_blockchain_base::AbstractBlockchain;
+use nimiq_bls::{KeyPair, SecretKey, SecureGenerate};
+use nimiq_bls::bls12_381::lazy::LazyPublicKey;
+use nimiq_collections::bitset::BitSet;
+use nimiq_database::volatile::VolatileEnvironment;
+use nimiq_hash::{Blake2bHash, Hash};
+use nimiq_mempool::{Mempool, MempoolConfig};
+use nimiq_network_primitives::networks::NetworkId;
+use nimiq_primitives::policy;
+use nimiq_primitives::slot::{Slot, ValidatorSlotBand};
+use nimiq_validator::slash::{ForkProofPool, ForkProofPoolError, ForkProofPoolEvent, ForkProofReport, InsertOutcome, MergeSummary, SlotResolver, SlotSnapshot};
+
+/// Secret key of validator. Tests run with `network-primitives/src/genesis/unit-albatross.toml`
+const SECRET_KEY: &'static str = "49ea68eb6b8afdf4ca4d4c0a0b295c76ca85225293693bc30e755476492b707f";
+
+fn key_pair() -> KeyPair {
+    KeyPair::from(SecretKey::deserialize_from_vec(&hex::decode(SECRET_KEY).unwrap()).unwrap())
+}
+
+/// Creates a blockchain with `num_blocks` micro blocks on top of the genesis block and returns
+/// those blocks.
+fn setup(num_blocks: u32) -> (Arc<Blockchain>, Vec<MicroBlock>) {
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = Arc::new(Blockchain::new(env, NetworkId::UnitAlbatross).unwrap());
+    let mempool = Mempool::new(Arc::clone(&blockchain), MempoolConfig::default());
+    let producer = BlockProducer::new(Arc::clone(&blockchain), mempool, key_pair());
+
+    let mut blocks = Vec::new();
+    for i in 1..=num_blocks {
+        let block = producer.next_micro_block(vec![], 1565713920000 + i as u64 * 2000, 0, vec![0x42], None);
+        assert_eq!(blockchain.push(Block::Micro(block.clone())), Ok(PushResult::Extended));
+        blocks.push(block);
+    }
+
+    (blockchain, blocks)
+}
+
+/// Creates a fork proof by signing a second header for the same block number and view number.
+fn fork_proof(block: &MicroBlock) -> ForkProof {
+    fork_proof_with_timestamp_offset(block, 1)
+}
+
+/// Like `fork_proof`, but lets the caller choose how the second header differs, to get different
+/// proofs for the same slot. The proof is returned in canonical form, i.e. as it is stored in the
+/// pool.
+fn fork_proof_with_timestamp_offset(block: &MicroBlock, offset: u64) -> ForkProof {
+    let header1 = block.header.clone();
+    let justification1 = block.justification.signature;
+    let mut header2 = header1.clone();
+    header2.timestamp += offset;
+    let justification2 = key_pair().sign(&header2).compress();
+    ForkProof::new(header1, justification1, header2, justification2).unwrap().canonical()
+}
+
+#[test]
+fn it_packs_fork_proofs_up_to_max_size() {
+    let (blockchain, blocks) = setup(3);
+    let mut pool = ForkProofPool::new(blockchain);
+    for block in blocks.iter() {
+        assert_eq!(pool.insert(fork_proof(block)), Ok(InsertOutcome::Added));
+    }
+
+    // All fork proofs have the same size, so a budget of exactly two proofs must fit two of them.
+    let proof_size = fork_proof(&blocks[0]).serialized_size();
+    assert_eq!(pool.get_fork_proofs_for_block(2 * proof_size).len(), 2);
+    assert_eq!(pool.get_fork_proofs_for_block(2 * proof_size - 1).len(), 1);
+    assert_eq!(pool.get_fork_proofs_for_block(3 * proof_size).len(), 3);
+    assert!(pool.get_fork_proofs_for_block(proof_size - 1).is_empty());
+}
+
+#[test]
+fn it_selects_fork_proofs_deterministically() {
+    let (blockchain, blocks) = setup(3);
+    let mut pool = ForkProofPool::new(blockchain);
+    for block in blocks.iter().rev() {
+        assert_eq!(pool.insert(fork_proof(block)), Ok(InsertOutcome::Added));
+    }
+
+    let proof_size = fork_proof(&blocks[0]).serialized_size();
+    let proofs = pool.get_fork_proofs_for_block(2 * proof_size);
+    assert_eq!(proofs, vec![fork_proof(&blocks[0]), fork_proof(&blocks[1])]);
+}
+
+#[test]
+fn it_previews_the_fork_proofs_for_a_block() {
+    let (blockchain, blocks) = setup(3);
+    let mut pool = ForkProofPool::new(blockchain);
+    for block in blocks.iter().rev() {
+        assert_eq!(pool.insert(fork_proof(block)), Ok(InsertOutcome::Added));
+    }
+
+    let proof_size = fork_proof(&blocks[0]).serialized_size();
+    let preview = pool.preview_for_block(2 * proof_size + 1);
+    let proofs = pool.get_fork_proofs_for_block(2 * proof_size + 1);
+    assert_eq!(preview, proofs.iter().map(|proof| (proof.hash::<Blake2bHash>(), proof.serialized_size())).collect::<Vec<_>>());
+    assert_eq!(preview.iter().map(|(_, size)| size).sum::<usize>(), 2 * proof_size);
+    assert!(pool.preview_for_block(proof_size - 1).is_empty());
+
+    // Previewing doesn't change the pool.
+    assert_eq!(pool.len(), 3);
+    assert_eq!(pool.drain_for_block(2 * proof_size + 1), proofs);
+}
+
+#[test]
+fn it_frees_the_slot_when_removing_a_fork_proof() {
+    let (blockchain, blocks) = setup(1);
+    let mut pool = ForkProofPool::new(blockchain);
+
+    let proof = fork_proof(&blocks[0]);
+    let other_proof = fork_proof_with_timestamp_offset(&blocks[0], 2);
+    assert_eq!(pool.insert(proof.clone()), Ok(InsertOutcome::Added));
+    assert_eq!(pool.insert(other_proof.clone()), Ok(InsertOutcome::SlotOccupied));
+    assert_eq!(pool.insert(proof.clone()), Ok(InsertOutcome::AlreadyKnown));
+
+    assert_eq!(pool.remove(&proof.hash::<Blake2bHash>()), Some(proof.clone()));
+    assert_eq!(pool.remove(&proof.hash::<Blake2bHash>()), None);
+    assert!(pool.is_empty());
+
+    assert_eq!(pool.insert(other_proof.clone()), Ok(InsertOutcome::Added));
+    assert!(pool.contains(&other_proof));
+    assert!(!pool.contains(&proof));
+}
+
+#[test]
+fn it_can_save_and_load_fork_proofs() {
+    let (blockchain, blocks) = setup(2);
+    let mut pool = ForkProofPool::new(Arc::clone(&blockchain));
+    for block in blocks.iter() {
+        assert_eq!(pool.insert(fork_proof(block)), Ok(InsertOutcome::Added));
+    }
+    let data = pool.save_to_vec();
+
+    let mut loaded_pool = ForkProofPool::new(blockchain);
+    assert_eq!(loaded_pool.load_from_vec(&data), Ok(2));
+    for block in blocks.iter() {
+        assert!(loaded_pool.contains(&fork_proof(block)));
+    }
+
+    // Loading again doesn't add duplicates.
+    assert_eq!(loaded_pool.load_from_vec(&data), Ok(0));
+    assert_eq!(loaded_pool.len(), 2);
+}
+
+#[test]
+fn it_lists_fork_proofs_by_epoch() {
+    let (blockchain, blocks) = setup(2);
+    let mut pool = ForkProofPool::new(blockchain);
+    for block in blocks.iter() {
+        assert_eq!(pool.insert(fork_proof(block)), Ok(InsertOutcome::Added));
+    }
+
+    assert_eq!(pool.proofs_for_epoch(policy::epoch_at(1)).len(), 2);
+    assert!(pool.proofs_for_epoch(policy::epoch_at(1) + 1).is_empty());
+}
+
+#[test]
+fn it_can_insert_fork_proofs_in_batches() {
+    let (blockchain, blocks) = setup(2);
+    let mut pool = ForkProofPool::new(blockchain);
+
+    let results = pool.insert_batch(vec![
+        fork_proof(&blocks[0]),
+        fork_proof(&blocks[1]),
+        fork_proof(&blocks[0]),
+        fork_proof_with_timestamp_offset(&blocks[1], 2),
+    ]);
+    assert_eq!(results, vec![
+        Ok(InsertOutcome::Added),
+        Ok(InsertOutcome::Added),
+        Ok(InsertOutcome::AlreadyKnown),
+        Ok(InsertOutcome::SlotOccupied),
+    ]);
+    assert_eq!(pool.len(), 2);
+}
+
+/// Resolves slots through the blockchain, counting snapshots and the lookups made outside of one.
+struct CountingResolver {
+    blockchain: Arc<Blockchain>,
+    snapshots: AtomicUsize,
+    direct_lookups: AtomicUsize,
+}
+
+impl SlotSnapshot for CountingResolver {
+    fn state_height(&self) -> u32 {
+        self.direct_lookups.fetch_add(1, Ordering::SeqCst);
+        self.blockchain.state_height()
+    }
+
+    fn slashed_set_for_epoch(&self, epoch: u32) -> Option<BitSet> {
+        self.direct_lookups.fetch_add(1, Ordering::SeqCst);
+        self.blockchain.slashed_set_for_epoch(epoch)
+    }
+
+    fn get_slot_at(&self, block_number: u32, view_number: u32) -> Option<(Slot, u16)> {
+        self.direct_lookups.fetch_add(1, Ordering::SeqCst);
+        self.blockchain.get_slot_at(block_number, view_number, None)
+    }
+}
+
+impl SlotResolver for CountingResolver {
+    fn with_snapshot<T, F: FnOnce(&dyn SlotSnapshot) -> T>(&self, f: F) -> T {
+        self.snapshots.fetch_add(1, Ordering::SeqCst);
+        self.blockchain.with_snapshot(f)
+    }
+}
+
+#[test]
+fn it_inserts_batches_against_a_single_snapshot() {
+    let (blockchain, blocks) = setup(3);
+    let resolver = Arc::new(CountingResolver { blockchain, snapshots: AtomicUsize::new(0), direct_lookups: AtomicUsize::new(0) });
+    let mut pool = ForkProofPool::new(Arc::clone(&resolver));
+
+    let results = pool.insert_batch(blocks.iter().map(fork_proof).collect());
+    assert_eq!(results, vec![Ok(InsertOutcome::Added); 3]);
+    assert_eq!(resolver.snapshots.load(Ordering::SeqCst), 1);
+    assert_eq!(resolver.direct_lookups.load(Ordering::SeqCst), 0);
+
+    // Merging validates against a single snapshot, too.
+    let mut other = ForkProofPool::new(Arc::clone(&resolver));
+    assert_eq!(other.merge(pool).added, 3);
+    assert_eq!(resolver.snapshots.load(Ordering::SeqCst), 2);
+    assert_eq!(resolver.direct_lookups.load(Ordering::SeqCst), 0);
+}
+
+#[test]
+fn it_tracks_pooled_slots() {
+    let (blockchain, blocks) = setup(1);
+    let (_, slot_number) = blockchain.get_slot_at(blocks[0].header.block_number, blocks[0].header.view_number, None).unwrap();
+    let mut pool = ForkProofPool::new(blockchain);
+    assert!(!pool.contains_slot(slot_number));
+
+    assert_eq!(pool.insert(fork_proof(&blocks[0])), Ok(InsertOutcome::Added));
+    assert!(pool.contains_slot(slot_number));
+}
+
+#[test]
+fn it_rejects_fork_proofs_for_different_slots() {
+    let (blockchain, blocks) = setup(1);
+    let mut pool = ForkProofPool::new(blockchain);
+
+    let mut proof = fork_proof(&blocks[0]);
+    proof.header2.view_number += 1;
+    proof.justification2 = key_pair().sign(&proof.header2).compress();
+    assert_eq!(pool.insert(proof), Err(ForkProofPoolError::UnexpectedBlock));
+    assert!(pool.is_empty());
+}
+
+#[test]
+fn it_only_constructs_consistent_fork_proofs() {
+    let (_, blocks) = setup(2);
+    let header = blocks[0].header.clone();
+    let justification = blocks[0].justification.signature;
+
+    assert_eq!(ForkProof::new(header.clone(), justification, header.clone(), justification), Err(ForkProofError::SameHeader));
+    assert_eq!(ForkProof::new(header.clone(), justification, blocks[1].header.clone(), blocks[1].justification.signature), Err(ForkProofError::SlotMismatch));
+
+    let proof = fork_proof(&blocks[0]);
+    assert!(proof.is_self_consistent());
+    let mut proof = ForkProof { header1: header.clone(), justification1: justification, header2: header, justification2: justification };
+    assert!(!proof.is_self_consistent());
+    // Rejecting such proofs in blocks would be a consensus change, so `verify` doesn't.
+    assert_eq!(proof.verify(&key_pair().public), Ok(()));
+    proof.header2.view_number += 1;
+    assert!(!proof.is_self_consistent());
+}
+
+#[test]
+fn it_caches_resolved_slots() {
+    let (blockchain, blocks) = setup(2);
+    let mut pool = ForkProofPool::new(blockchain);
+
+    assert_eq!(pool.insert(fork_proof(&blocks[0])), Ok(InsertOutcome::Added));
+    assert_eq!(pool.slot_cache_hits(), 0);
+    assert_eq!(pool.insert(fork_proof_with_timestamp_offset(&blocks[0], 2)), Ok(InsertOutcome::SlotOccupied));
+    assert_eq!(pool.slot_cache_hits(), 1);
+
+    // Applying a block invalidates the cache.
+    pool.apply_block(&Block::Micro(blocks[1].clone()));
+    assert_eq!(pool.insert(fork_proof_with_timestamp_offset(&blocks[0], 3)), Ok(InsertOutcome::SlotOccupied));
+    assert_eq!(pool.slot_cache_hits(), 1);
+}
+
+#[test]
+fn it_rejects_fork_proofs_below_min_slot_weight() {
+    let (blockchain, blocks) = setup(2);
+    let mut pool = ForkProofPool::new(blockchain);
+    assert_eq!(pool.min_slot_weight(), 0);
+
+    // The only validator of the unit test genesis holds all slots.
+    pool.set_min_slot_weight(policy::SLOTS + 1);
+    assert_eq!(pool.insert(fork_proof(&blocks[0])), Err(ForkProofPoolError::InsufficientWeight));
+    assert!(pool.is_empty());
+
+    pool.set_min_slot_weight(policy::SLOTS);
+    assert_eq!(pool.insert(fork_proof(&blocks[1])), Ok(InsertOutcome::Added));
+}
+
+#[test]
+fn it_drains_fork_proofs_for_block() {
+    let (blockchain, blocks) = setup(3);
+    let mut pool = ForkProofPool::new(blockchain);
+    for block in blocks.iter() {
+        assert_eq!(pool.insert(fork_proof(block)), Ok(InsertOutcome::Added));
+    }
+
+    let proof_size = fork_proof(&blocks[0]).serialized_size();
+    let selected = pool.get_fork_proofs_for_block(2 * proof_size);
+    let drained = pool.drain_for_block(2 * proof_size);
+    assert_eq!(drained, selected);
+    assert_eq!(pool.len(), 1);
+    for proof in drained.iter() {
+        assert!(!pool.contains(proof));
+    }
+
+    // The remaining proof is selected next, and drained proofs can be re-inserted.
+    assert_eq!(pool.drain_for_block(2 * proof_size), vec![fork_proof(&blocks[2])]);
+    assert!(pool.is_empty());
+    for proof in drained {
+        assert_eq!(pool.insert(proof), Ok(InsertOutcome::Added));
+    }
+}
+
+/// Resolves every block to the same slot, with a configurable chain height and slashed set.
+struct MockResolver {
+    height: u32,
+    slot: (Slot, u16),
+    slashed_set: BitSet,
+}
+
+impl SlotResolver for MockResolver {}
+
+impl SlotSnapshot for MockResolver {
+    fn state_height(&self) -> u32 {
+        self.height
+    }
+
+    fn slashed_set_for_epoch(&self, epoch: u32) -> Option<BitSet> {
+        let current_epoch = policy::epoch_at(self.height);
+        if epoch == current_epoch || epoch + 1 == current_epoch {
+            Some(self.slashed_set.clone())
+        } else {
+            None
+        }
+    }
+
+    fn get_slot_at(&self, _block_number: u32, _view_number: u32) -> Option<(Slot, u16)> {
+        Some(self.slot.clone())
+    }
+}
+
+#[test]
+fn it_can_use_a_mock_slot_resolver() {
+    let (blockchain, blocks) = setup(1);
+    let slot = blockchain.get_slot_at(blocks[0].header.block_number, blocks[0].header.view_number, None).unwrap();
+    let slot_number = slot.1;
+    let proof = fork_proof(&blocks[0]);
+
+    let mut pool = ForkProofPool::new(Arc::new(MockResolver { height: 1, slot: slot.clone(), slashed_set: BitSet::new() }));
+    assert_eq!(pool.insert(proof.clone()), Ok(InsertOutcome::Added));
+    assert!(pool.contains_slot(slot_number));
+
+    let mut slashed_set = BitSet::new();
+    slashed_set.insert(slot_number as usize);
+    let mut pool = ForkProofPool::new(Arc::new(MockResolver { height: 1, slot: slot.clone(), slashed_set }));
+    assert_eq!(pool.insert(proof.clone()), Err(ForkProofPoolError::SlotAlreadySlashed));
+
+    let height = policy::EPOCH_LENGTH * 2 + 1;
+    let mut pool = ForkProofPool::new(Arc::new(MockResolver { height, slot, slashed_set: BitSet::new() }));
+    assert_eq!(pool.insert(proof), Err(ForkProofPoolError::InvalidEpochTarget));
+}
+
+#[test]
+fn it_does_housekeeping_with_slashed_sets_from_chain() {
+    let (blockchain, blocks) = setup(2);
+    let mut pool = ForkProofPool::new(Arc::clone(&blockchain));
+    for block in blocks.iter() {
+        assert_eq!(pool.insert(fork_proof(block)), Ok(InsertOutcome::Added));
+    }
+
+    // Nothing has been slashed yet, so both fork proofs stay.
+    assert_eq!(pool.housekeeping_from_chain(blockchain.block_number()), 0);
+    assert_eq!(pool.len(), 2);
+
+    // Two epochs later, the fork proofs can't be included anymore.
+    assert_eq!(pool.housekeeping_from_chain(policy::EPOCH_LENGTH * 2 + 1), 2);
+    assert!(pool.is_empty());
+}
+
+#[test]
+fn it_does_housekeeping_for_a_range_of_blocks() {
+    let (blockchain, blocks) = setup(3);
+    let mut pool = ForkProofPool::new(Arc::clone(&blockchain));
+    for block in blocks.iter() {
+        assert_eq!(pool.insert(fork_proof(block)), Ok(InsertOutcome::Added));
+    }
+    let slot_numbers: Vec<u16> = blocks.iter()
+        .map(|block| blockchain.get_slot_at(block.header.block_number, block.header.view_number, None).unwrap().1)
+        .collect();
+
+    // The first slot is slashed during the first epoch, the second one only after the second
+    // epoch started.
+    let epoch = policy::epoch_at(blocks[0].header.block_number);
+    let start = blockchain.block_number() + 1;
+    let up_to_block = policy::EPOCH_LENGTH + 5;
+    let slashed_set = |slot_numbers: &[u16]| -> BitSet {
+        slot_numbers.iter().map(|slot_number| *slot_number as usize).collect()
+    };
+    let history = |block_number: u32| -> (BitSet, BitSet) {
+        if block_number < 10 {
+            (BitSet::new(), BitSet::new())
+        } else if policy::epoch_at(block_number) == epoch {
+            (slashed_set(&slot_numbers[..1]), BitSet::new())
+        } else {
+            (BitSet::new(), slashed_set(&slot_numbers[..2]))
+        }
+    };
+
+    let mut expected_pool = pool.clone();
+    let mut num_removed = 0;
+    for block_number in start..=up_to_block {
+        let (current_slashed_set, previous_slashed_set) = history(block_number);
+        num_removed += expected_pool.housekeeping(block_number, &current_slashed_set, &previous_slashed_set);
+    }
+
+    let mut slashed_sets = HashMap::new();
+    slashed_sets.insert(epoch, slashed_set(&slot_numbers[..2]));
+    slashed_sets.insert(epoch + 1, BitSet::new());
+    assert_eq!(pool.housekeeping_range(up_to_block, &slashed_sets), num_removed);
+    assert_eq!(num_removed, 2);
+    assert_eq!(pool.get_all(), expected_pool.get_all());
+    assert_eq!(pool.get_all(), vec![fork_proof(&blocks[2])]);
+
+    // Two epochs later, the remaining proof can't be included anymore.
+    assert_eq!(pool.housekeeping_range(policy::EPOCH_LENGTH * 2 + 1, &HashMap::new()), 1);
+    assert!(pool.is_empty());
+}
+
+#[test]
+fn it_reports_pooled_fork_proofs() {
+    let (blockchain, blocks) = setup(2);
+    let mut pool = ForkProofPool::new(Arc::clone(&blockchain));
+    assert!(pool.report().is_empty());
+
+    for block in blocks.iter().rev() {
+        assert_eq!(pool.insert(fork_proof(block)), Ok(InsertOutcome::Added));
+    }
+
+    let expected: Vec<ForkProofReport> = blocks.iter()
+        .map(|block| {
+            let (_, slot_number) = blockchain.get_slot_at(block.header.block_number, block.header.view_number, None).unwrap();
+            ForkProofReport {
+                hash: fork_proof(block).hash(),
+                slot_number,
+                block_number: block.header.block_number,
+                view_number: block.header.view_number,
+                epoch: policy::epoch_at(block.header.block_number),
+            }
+        })
+        .collect();
+    assert_eq!(pool.report(), expected);
+}
+
+#[test]
+fn it_inserts_fork_proofs_with_pre_resolved_slot() {
+    let (blockchain, blocks) = setup(2);
+    let (slot, slot_number) = blockchain.get_slot_at(blocks[0].header.block_number, blocks[0].header.view_number, None).unwrap();
+    let mut pool = ForkProofPool::new(Arc::clone(&blockchain));
+
+    // The signature is still verified.
+    let mut forged = fork_proof(&blocks[0]);
+    forged.justification2 = forged.justification1;
+    assert_eq!(pool.insert_with_slot(forged, &slot, slot_number), Err(ForkProofPoolError::InvalidForkProof(ForkProofError::InvalidJustification)));
+
+    assert_eq!(pool.insert_with_slot(fork_proof(&blocks[0]), &slot, slot_number), Ok(InsertOutcome::Added));
+    assert!(pool.contains_slot(slot_number));
+    assert_eq!(pool.slot_cache_hits(), 0);
+
+    // The slashed set is still checked.
+    let mut slashed_set = BitSet::new();
+    slashed_set.insert(slot_number as usize);
+    let mut pool = ForkProofPool::new(Arc::new(MockResolver { height: 2, slot: (slot.clone(), slot_number), slashed_set }));
+    assert_eq!(pool.insert_with_slot(fork_proof(&blocks[0]), &slot, slot_number), Err(ForkProofPoolError::SlotAlreadySlashed));
+}
+
+#[test]
+fn it_clones_pools_sharing_the_blockchain() {
+    let (blockchain, blocks) = setup(2);
+    let mut pool = ForkProofPool::new(Arc::clone(&blockchain));
+    assert_eq!(pool.insert(fork_proof(&blocks[0])), Ok(InsertOutcome::Added));
+
+    let num_refs = Arc::strong_count(&blockchain);
+    let mut clone = pool.clone();
+    assert_eq!(Arc::strong_count(&blockchain), num_refs + 1);
+    assert!(clone.contains(&fork_proof(&blocks[0])));
+
+    // The pools are independent afterwards.
+    assert_eq!(clone.insert(fork_proof(&blocks[1])), Ok(InsertOutcome::Added));
+    assert_eq!(clone.len(), 2);
+    assert_eq!(pool.len(), 1);
+
+    let (_, slot_number) = blockchain.get_slot_at(blocks[0].header.block_number, blocks[0].header.view_number, None).unwrap();
+    assert_eq!(format!("{:?}", pool), format!("ForkProofPool {{ num_proofs: 1, slots: [{}] }}", slot_number));
+}
+
+#[test]
+fn it_notifies_listener_of_changes() {
+    let (blockchain, blocks) = setup(2);
+    let (_, slot_number) = blockchain.get_slot_at(blocks[0].header.block_number, blocks[0].header.view_number, None).unwrap();
+    let mut pool = ForkProofPool::new(blockchain);
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let events1 = Arc::clone(&events);
+    pool.set_listener(Box::new(move |event| events1.lock().unwrap().push(event)));
+
+    let proof = fork_proof(&blocks[0]);
+    let hash: Blake2bHash = proof.hash();
+    assert_eq!(pool.insert(proof.clone()), Ok(InsertOutcome::Added));
+    assert_eq!(pool.insert(proof.clone()), Ok(InsertOutcome::AlreadyKnown));
+    assert_eq!(*events.lock().unwrap(), vec![ForkProofPoolEvent::Added(hash.clone(), slot_number)]);
+
+    // Including the proof in a block removes it, reverting that block adds it back.
+    let mut block = blocks[1].clone();
+    block.extrinsics.as_mut().unwrap().fork_proofs = vec![proof];
+    let block = Block::Micro(block);
+    pool.apply_block(&block);
+    pool.revert_block(&block);
+    assert_eq!(*events.lock().unwrap(), vec![
+        ForkProofPoolEvent::Added(hash.clone(), slot_number),
+        ForkProofPoolEvent::Removed(hash.clone(), slot_number),
+        ForkProofPoolEvent::Added(hash.clone(), slot_number),
+    ]);
+
+    // Housekeeping removes it once its epoch can't be slashed anymore.
+    events.lock().unwrap().clear();
+    assert_eq!(pool.housekeeping(policy::EPOCH_LENGTH * 2 + 1, &BitSet::new(), &BitSet::new()), 1);
+    assert_eq!(*events.lock().unwrap(), vec![ForkProofPoolEvent::Removed(hash, slot_number)]);
+}
+
+#[test]
+fn it_skips_occupied_slots_when_reverting_blocks() {
+    let (blockchain, blocks) = setup(2);
+    let mut pool = ForkProofPool::new(blockchain);
+    let pooled = fork_proof_with_timestamp_offset(&blocks[0], 2);
+    assert_eq!(pool.insert(pooled.clone()), Ok(InsertOutcome::Added));
+
+    // The reverted block contains a different proof for the same slot.
+    let mut block = blocks[1].clone();
+    block.extrinsics.as_mut().unwrap().fork_proofs = vec![fork_proof(&blocks[0])];
+    pool.revert_block(&Block::Micro(block));
+    assert_eq!(pool.len(), 1);
+    assert!(pool.contains(&pooled));
+
+    // Removing the pooled proof frees the slot.
+    pool.remove(&pooled.hash::<Blake2bHash>());
+    assert!(pool.slashable_slots().is_empty());
+}
+
+#[test]
+fn it_respects_capacity_when_reverting_blocks() {
+    let (blockchain, blocks) = setup(3);
+    let mut pool = ForkProofPool::with_capacity(Arc::clone(&blockchain), 1);
+    assert_eq!(pool.insert(fork_proof(&blocks[1])), Ok(InsertOutcome::Added));
+
+    // A proof of equal weight doesn't fit into the full pool.
+    let mut block = blocks[2].clone();
+    block.extrinsics.as_mut().unwrap().fork_proofs = vec![fork_proof(&blocks[0])];
+    let block = Block::Micro(block);
+    pool.revert_block(&block);
+    assert_eq!(pool.len(), 1);
+    assert!(pool.contains(&fork_proof(&blocks[1])));
+
+    // It evicts a proof against a validator with fewer slots.
+    pool.remove(&fork_proof(&blocks[1]).hash::<Blake2bHash>());
+    let (slot, slot_number) = blockchain.get_slot_at(blocks[1].header.block_number, blocks[1].header.view_number, None).unwrap();
+    let light_slot = Slot {
+        validator_slot: ValidatorSlotBand::new(LazyPublicKey::from(key_pair().public), 1),
+        stake_slot: slot.stake_slot,
+    };
+    assert_eq!(pool.insert_with_slot(fork_proof(&blocks[1]), &light_slot, slot_number), Ok(InsertOutcome::Added));
+    pool.revert_block(&block);
+    assert_eq!(pool.len(), 1);
+    assert!(pool.contains(&fork_proof(&blocks[0])));
+}
+
+#[test]
+fn it_validates_fork_proofs_without_inserting() {
+    let (blockchain, blocks) = setup(2);
+    let (_, slot_number) = blockchain.get_slot_at(blocks[0].header.block_number, blocks[0].header.view_number, None).unwrap();
+    let pool = ForkProofPool::new(Arc::clone(&blockchain));
+
+    assert_eq!(pool.validate(&fork_proof(&blocks[0])), Ok(slot_number));
+    assert!(pool.is_empty());
+    assert_eq!(pool.slot_cache_hits(), 0);
+
+    let mut forged = fork_proof(&blocks[0]);
+    forged.justification2 = forged.justification1;
+    assert_eq!(pool.validate(&forged), Err(ForkProofPoolError::InvalidForkProof(ForkProofError::InvalidJustification)));
+
+    // A different proof for a slot that is already pooled is still valid.
+    let mut pool = ForkProofPool::new(blockchain);
+    assert_eq!(pool.insert(fork_proof(&blocks[0])), Ok(InsertOutcome::Added));
+    assert_eq!(pool.validate(&fork_proof_with_timestamp_offset(&blocks[0], 2)), Ok(slot_number));
+}
+
+#[test]
+fn it_resolves_the_accused_validator() {
+    let (blockchain, blocks) = setup(1);
+    let mut pool = ForkProofPool::new(blockchain);
+    let proof = fork_proof(&blocks[0]);
+    let hash: Blake2bHash = proof.hash();
+    assert_eq!(pool.accused_validator(&hash), None);
+
+    assert_eq!(pool.insert(proof), Ok(InsertOutcome::Added));
+    assert_eq!(pool.accused_validator(&hash), Some(key_pair().public));
+}
+
+#[test]
+fn it_sums_serialized_sizes() {
+    let (blockchain, blocks) = setup(3);
+    let mut pool = ForkProofPool::new(blockchain);
+    assert_eq!(pool.total_serialized_size(), 0);
+
+    for block in blocks.iter() {
+        assert_eq!(pool.insert(fork_proof(block)), Ok(InsertOutcome::Added));
+    }
+    let total_size: usize = blocks.iter().map(|block| fork_proof(block).serialized_size()).sum();
+    assert_eq!(pool.total_serialized_size(), total_size);
+    assert_eq!(pool.get_fork_proofs_for_block(total_size).len(), 3);
+}
+
+#[test]
+fn it_exposes_slashable_slots() {
+    let (blockchain, blocks) = setup(3);
+    let mut pool = ForkProofPool::new(Arc::clone(&blockchain));
+    assert!(pool.slashable_slots().is_empty());
+
+    for block in blocks.iter() {
+        assert_eq!(pool.insert(fork_proof(block)), Ok(InsertOutcome::Added));
+    }
+    let slots: HashSet<u16> = blocks.iter()
+        .map(|block| blockchain.get_slot_at(block.header.block_number, block.header.view_number, None).unwrap().1)
+        .collect();
+    assert_eq!(*pool.slashable_slots(), slots);
+
+    pool.remove(&fork_proof(&blocks[0]).hash());
+    assert_eq!(pool.slashable_slots().len(), 2);
+}
+
+#[test]
+fn it_returns_evicted_fork_proofs() {
+    let (blockchain, blocks) = setup(3);
+    let mut pool = ForkProofPool::with_capacity(Arc::clone(&blockchain), 1);
+
+    // Insert a proof against a validator with a single slot, so that it gets evicted by a proof
+    // against the genesis validator, which holds all slots.
+    let (slot, slot_number) = blockchain.get_slot_at(blocks[0].header.block_number, blocks[0].header.view_number, None).unwrap();
+    let light_slot = Slot {
+        validator_slot: ValidatorSlotBand::new(LazyPublicKey::from(key_pair().public), 1),
+        stake_slot: slot.stake_slot,
+    };
+    let light_proof = fork_proof(&blocks[0]);
+    assert_eq!(pool.insert_with_slot(light_proof.clone(), &light_slot, slot_number), Ok(InsertOutcome::Added));
+
+    let (outcome, evicted) = pool.insert_evicting(fork_proof(&blocks[1])).unwrap();
+    assert_eq!(outcome, InsertOutcome::Added);
+    assert_eq!(evicted.map(|fork_proof| fork_proof.hash::<Blake2bHash>()), Some(light_proof.hash()));
+
+    // Proofs of equal weight don't evict each other.
+    assert_eq!(pool.insert_evicting(fork_proof(&blocks[2])).unwrap_err(), ForkProofPoolError::PoolFull);
+    let (outcome, evicted) = pool.insert_evicting(fork_proof(&blocks[1])).unwrap();
+    assert_eq!(outcome, InsertOutcome::AlreadyKnown);
+    assert!(evicted.is_none());
+}
+
+#[test]
+fn it_retains_fork_proofs_of_given_epochs() {
+    let (blockchain, blocks) = setup(2);
+    let mut pool = ForkProofPool::new(blockchain);
+    for block in blocks.iter() {
+        assert_eq!(pool.insert(fork_proof(block)), Ok(InsertOutcome::Added));
+    }
+
+    let epoch = policy::epoch_at(blocks[0].header.block_number);
+    assert_eq!(pool.retain_epochs(&[epoch, epoch + 1]), 0);
+    assert_eq!(pool.len(), 2);
+
+    assert_eq!(pool.retain_epochs(&[epoch + 1]), 2);
+    assert!(pool.is_empty());
+    assert!(pool.slashable_slots().is_empty());
+}
+
+#[test]
+fn it_detects_fork_proofs_with_swapped_headers() {
+    let (blockchain, blocks) = setup(1);
+    let mut pool = ForkProofPool::new(blockchain);
+
+    let proof = fork_proof(&blocks[0]);
+    let swapped = ForkProof::new(proof.header2.clone(), proof.justification2, proof.header1.clone(), proof.justification1).unwrap();
+    assert_ne!(swapped.hash::<Blake2bHash>(), proof.hash::<Blake2bHash>());
+    assert_eq!(swapped.clone().canonical().hash::<Blake2bHash>(), proof.hash::<Blake2bHash>());
+    assert_eq!(swapped.canonical_hash(), proof.hash::<Blake2bHash>());
+    assert_eq!(proof.canonical_hash(), proof.hash::<Blake2bHash>());
+
+    assert_eq!(pool.insert(swapped.clone()), Ok(InsertOutcome::Added));
+    assert_eq!(pool.insert(proof.clone()), Ok(InsertOutcome::AlreadyKnown));
+    assert_eq!(pool.len(), 1);
+    assert!(pool.contains(&swapped));
+    assert!(pool.contains(&proof));
+    assert_eq!(pool.remove(&proof.hash::<Blake2bHash>()), Some(proof));
+}
+
+#[test]
+fn it_sums_pending_slash_weight() {
+    let (blockchain, blocks) = setup(2);
+    let mut pool = ForkProofPool::new(blockchain);
+    assert_eq!(pool.pending_slash_weight(), 0);
+
+    for block in blocks.iter() {
+        assert_eq!(pool.insert(fork_proof(block)), Ok(InsertOutcome::Added));
+    }
+    // The only validator holds all slots. It has proofs in two of them, but can only lose its
+    // slots once.
+    assert_eq!(pool.slashable_slots().len(), 2);
+    assert_eq!(pool.pending_slash_weight(), policy::SLOTS);
+
+    // Proofs for an occupied slot aren't counted.
+    assert_eq!(pool.insert(fork_proof_with_timestamp_offset(&blocks[0], 2)), Ok(InsertOutcome::SlotOccupied));
+    assert_eq!(pool.pending_slash_weight(), policy::SLOTS);
+
+    pool.remove(&fork_proof(&blocks[0]).hash::<Blake2bHash>());
+    assert_eq!(pool.pending_slash_weight(), policy::SLOTS);
+
+    pool.remove(&fork_proof(&blocks[1]).hash::<Blake2bHash>());
+    assert_eq!(pool.pending_slash_weight(), 0);
+}
+
+/// Resolves slots through the blockchain, unless a slot has been set explicitly.
+struct OverridingResolver {
+    blockchain: Arc<Blockchain>,
+    slot: Mutex<Option<(Slot, u16)>>,
+}
+
+impl SlotResolver for OverridingResolver {}
+
+impl SlotSnapshot for OverridingResolver {
+    fn state_height(&self) -> u32 {
+        self.blockchain.state_height()
+    }
+
+    fn slashed_set_for_epoch(&self, epoch: u32) -> Option<BitSet> {
+        self.blockchain.slashed_set_for_epoch(epoch)
+    }
+
+    fn get_slot_at(&self, block_number: u32, view_number: u32) -> Option<(Slot, u16)> {
+        self.slot.lock().unwrap().clone()
+            .or_else(|| self.blockchain.get_slot_at(block_number, view_number, None))
+    }
+}
+
+#[test]
+fn it_revalidates_fork_proofs() {
+    let (blockchain, blocks) = setup(2);
+    let resolver = Arc::new(OverridingResolver { blockchain: Arc::clone(&blockchain), slot: Mutex::new(None) });
+    let mut pool = ForkProofPool::new(Arc::clone(&resolver));
+    for block in blocks.iter() {
+        assert_eq!(pool.insert(fork_proof(block)), Ok(InsertOutcome::Added));
+    }
+    assert!(pool.revalidate().is_empty());
+    assert_eq!(pool.len(), 2);
+
+    // After the validator set changed, the slots belong to a different validator, so the
+    // signatures don't verify anymore.
+    let (slot, slot_number) = blockchain.get_slot_at(blocks[0].header.block_number, blocks[0].header.view_number, None).unwrap();
+    let other_slot = Slot {
+        validator_slot: ValidatorSlotBand::new(LazyPublicKey::from(KeyPair::generate_default_csprng().public), slot.validator_slot.num_slots()),
+        stake_slot: slot.stake_slot,
+    };
+    *resolver.slot.lock().unwrap() = Some((other_slot, slot_number));
+
+    let mut dropped = pool.revalidate();
+    dropped.sort();
+    let mut expected: Vec<Blake2bHash> = blocks.iter().map(|block| fork_proof(block).hash::<Blake2bHash>()).collect();
+    expected.sort();
+    assert_eq!(dropped, expected);
+    assert!(pool.is_empty());
+    assert!(pool.slashable_slots().is_empty());
+}
+
+#[test]
+fn it_merges_fork_proof_pools() {
+    let (blockchain, blocks) = setup(3);
+    let mut pool = ForkProofPool::with_capacity(Arc::clone(&blockchain), 2);
+    assert_eq!(pool.insert(fork_proof(&blocks[0])), Ok(InsertOutcome::Added));
+
+    let mut other = ForkProofPool::new(Arc::clone(&blockchain));
+    assert_eq!(other.insert(fork_proof_with_timestamp_offset(&blocks[0], 2)), Ok(InsertOutcome::Added));
+    assert_eq!(other.insert(fork_proof(&blocks[1])), Ok(InsertOutcome::Added));
+    assert_eq!(other.insert(fork_proof(&blocks[2])), Ok(InsertOutcome::Added));
+
+    // The slot of the first block is occupied, and only one of the other proofs fits.
+    let summary = pool.merge(other);
+    assert_eq!(summary, MergeSummary { added: 1, ignored: 1, rejected: 1 });
+    assert_eq!(pool.len(), 2);
+    assert!(pool.contains(&fork_proof(&blocks[0])));
+}
+
+#[test]
+fn it_finds_fork_proofs_by_validator() {
+    let (blockchain, blocks) = setup(1);
+    let mut pool = ForkProofPool::new(blockchain);
+    assert!(!pool.contains_for_validator(&key_pair().public));
+
+    assert_eq!(pool.insert(fork_proof(&blocks[0])), Ok(InsertOutcome::Added));
+    assert!(pool.contains_for_validator(&key_pair().public));
+    assert!(!pool.contains_for_validator(&KeyPair::generate_default_csprng().public));
+}
+
+#[test]
+fn it_returns_all_fork_proofs_in_order() {
+    let (blockchain, blocks) = setup(3);
+    let mut pool = ForkProofPool::new(blockchain);
+    assert!(pool.get_all().is_empty());
+
+    for block in blocks.iter().rev() {
+        assert_eq!(pool.insert(fork_proof(block)), Ok(InsertOutcome::Added));
+    }
+    let expected: Vec<ForkProof> = blocks.iter().map(fork_proof).collect();
+    assert_eq!(pool.get_all(), expected);
+    assert_eq!(pool.get_all(), pool.get_fork_proofs_for_block(usize::max_value()));
+}
+
+#[test]
+fn it_finds_the_oldest_and_newest_fork_proof() {
+    let (blockchain, blocks) = setup(3);
+    let mut pool = ForkProofPool::new(blockchain);
+    assert_eq!(pool.oldest_proof(), None);
+    assert_eq!(pool.newest_proof(), None);
+
+    assert_eq!(pool.insert(fork_proof(&blocks[1])), Ok(InsertOutcome::Added));
+    assert_eq!(pool.oldest_proof(), Some(&fork_proof(&blocks[1])));
+    assert_eq!(pool.newest_proof(), Some(&fork_proof(&blocks[1])));
+
+    assert_eq!(pool.insert(fork_proof(&blocks[2])), Ok(InsertOutcome::Added));
+    assert_eq!(pool.insert(fork_proof(&blocks[0])), Ok(InsertOutcome::Added));
+    assert_eq!(pool.oldest_proof(), Some(&fork_proof(&blocks[0])));
+    assert_eq!(pool.newest_proof(), Some(&fork_proof(&blocks[2])));
+
+    assert!(pool.remove(&fork_proof(&blocks[0]).hash::<Blake2bHash>()).is_some());
+    assert_eq!(pool.oldest_proof(), Some(&fork_proof(&blocks[1])));
+}
+
+#[test]
+fn it_tracks_the_age_of_fork_proofs() {
+    let (blockchain, blocks) = setup(3);
+    let mut pool = ForkProofPool::new(Arc::clone(&blockchain));
+
+    let proof = fork_proof(&blocks[0]);
+    let hash: Blake2bHash = proof.hash();
+    assert_eq!(pool.proof_age(&hash, 1000), None);
+    assert_eq!(pool.insert_with_timestamp(proof.clone(), 1000), Ok(InsertOutcome::Added));
+    assert_eq!(pool.proof_age(&hash, 1000), Some(0));
+    assert_eq!(pool.proof_age(&hash, 3500), Some(2500));
+    assert_eq!(pool.proof_age(&hash, 500), Some(0));
+
+    // Re-inserting a known proof doesn't reset its age.
+    assert_eq!(pool.insert_with_timestamp(proof.clone(), 2000), Ok(InsertOutcome::AlreadyKnown));
+    assert_eq!(pool.proof_age(&hash, 3500), Some(2500));
+
+    // Proofs inserted without a timestamp count from 0.
+    let other_proof = fork_proof(&blocks[1]);
+    assert_eq!(pool.insert(other_proof.clone()), Ok(InsertOutcome::Added));
+    assert_eq!(pool.proof_age(&other_proof.hash(), 3500), Some(3500));
+
+    // Merged proofs keep their timestamp.
+    let mut other_pool = ForkProofPool::new(blockchain);
+    let merged_proof = fork_proof(&blocks[2]);
+    assert_eq!(other_pool.insert_with_timestamp(merged_proof.clone(), 3000), Ok(InsertOutcome::Added));
+    pool.merge(other_pool);
+    assert_eq!(pool.proof_age(&merged_proof.hash(), 3500), Some(500));
+
+    pool.remove(&hash);
+    assert_eq!(pool.proof_age(&hash, 3500), None);
+}
+
+#[test]
+fn it_evicts_light_and_stale_fork_proofs_first() {
+    let (blockchain, blocks) = setup(6);
+
+    // Weight dominates, then view number and block number.
+    let proof = fork_proof(&blocks[1]);
+    assert!(proof.eviction_priority(2) > fork_proof(&blocks[2]).eviction_priority(1));
+    assert!(proof.eviction_priority(1) > fork_proof(&blocks[0]).eviction_priority(1));
+    let mut later_view = proof.clone();
+    later_view.header1.view_number += 1;
+    assert!(later_view.eviction_priority(1) > fork_proof(&blocks[2]).eviction_priority(1));
+
+    // Insert light proofs, weighing 1, 1 and 2 slots, then heavy ones that evict them.
+    let mut pool = ForkProofPool::with_capacity(Arc::clone(&blockchain), 3);
+    let light_proofs: Vec<ForkProof> = blocks[..3].iter().map(fork_proof).collect();
+    for (block, weight) in blocks[..3].iter().zip(&[1, 1, 2]) {
+        let (slot, slot_number) = blockchain.get_slot_at(block.header.block_number, block.header.view_number, None).unwrap();
+        let light_slot = Slot {
+            validator_slot: ValidatorSlotBand::new(LazyPublicKey::from(key_pair().public), *weight),
+            stake_slot: slot.stake_slot,
+        };
+        assert_eq!(pool.insert_with_slot(fork_proof(block), &light_slot, slot_number), Ok(InsertOutcome::Added));
+    }
+
+    let evicted: Vec<ForkProof> = blocks[3..].iter()
+        .map(|block| pool.insert_evicting(fork_proof(block)).unwrap().1.unwrap())
+        .collect();
+    assert_eq!(evicted, light_proofs);
+}
+
+#[test]
+fn it_clears_the_pool() {
+    let (blockchain, blocks) = setup(2);
+    let mut pool = ForkProofPool::new(blockchain);
+    for block in blocks.iter() {
+        assert_eq!(pool.insert(fork_proof(block)), Ok(InsertOutcome::Added));
+    }
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let events1 = Arc::clone(&events);
+    pool.set_listener(Box::new(move |event| events1.lock().unwrap().push(event)));
+
+    pool.clear();
+    assert_eq!(pool.len(), 0);
+    assert!(pool.slashable_slots().is_empty());
+    let removed = events.lock().unwrap().iter()
+        .filter(|event| match event { ForkProofPoolEvent::Removed(..) => true, _ => false })
+        .count();
+    assert_eq!(removed, 2);
+
+    // The pool can be used again.
+    assert_eq!(pool.insert(fork_proof(&blocks[0])), Ok(InsertOutcome::Added));
+    assert_eq!(pool.len(), 1);
+}